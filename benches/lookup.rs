@@ -0,0 +1,47 @@
+/*
+ * Compare linear and indexed option lookups on a frame with 30 options
+ *
+ * Run with `cargo bench`
+ */
+
+extern crate dhcp;
+
+use std::time::Instant;
+
+use dhcp::common::{Frame, Option, Opcode};
+
+const LOOKUPS: usize = 100000;
+
+fn main() {
+    let mut frame = Frame::new(Opcode::Request, 0);
+
+    for tag in 1..31 {
+        let mut opt = Option::new(tag);
+        opt.set_data_u8(tag);
+        frame.add_option(opt);
+    }
+
+    // Tags near the end of the list, the worst case of a linear scan
+    let tags = [28, 29, 30, 53];
+    let mut found = 0u64;
+
+    let start = Instant::now();
+
+    for i in 0..LOOKUPS {
+        found += frame.option(tags[i % tags.len()]).is_some() as u64;
+    }
+
+    let linear = start.elapsed();
+    let start = Instant::now();
+    let indexed = frame.indexed();
+
+    for i in 0..LOOKUPS {
+        found += indexed.option(tags[i % tags.len()]).is_some() as u64;
+    }
+
+    let index = start.elapsed();
+
+    println!("Frame::option        {} lookups in {:?}", LOOKUPS, linear);
+    println!("IndexedFrame::option {} lookups in {:?}", LOOKUPS, index);
+    println!("(checksum {})", found);
+}
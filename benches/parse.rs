@@ -0,0 +1,95 @@
+/*
+ * Compare owned, reused and borrowed frame parsing over a batch of packets,
+ * counting allocations
+ *
+ * Run with `cargo bench`
+ */
+
+extern crate dhcp;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use dhcp::common::{Frame, Option, MessageType, Opcode};
+use dhcp::frame_ref::FrameRef;
+use dhcp::writer::FrameBuilder;
+
+const PACKETS: usize = 10000;
+
+/*
+ * System allocator counting allocations
+ */
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations() -> usize {
+    ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+fn main() {
+    let packets: Vec<Vec<u8>> = (0..PACKETS as u32).map(|xid| {
+        FrameBuilder::new(Opcode::Request, xid)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(MessageType::Discover)
+            .option(Option::parameter_request_list(&[1, 3, 6, 15, 51, 54]).unwrap())
+            .build()
+            .and_then(|frame| frame.to_bytes())
+            .unwrap()
+    }).collect();
+
+    let start = Instant::now();
+    let before = allocations();
+    let mut xids = 0u64;
+
+    for packet in &packets {
+        let frame = Frame::parse(packet).unwrap();
+        xids += frame.xid as u64 + frame.options.len() as u64;
+    }
+
+    let owned = (start.elapsed(), allocations() - before);
+    let start = Instant::now();
+    let before = allocations();
+    let mut frame = Frame::parse(&packets[0]).unwrap();
+
+    for packet in &packets {
+        Frame::parse_into(packet, &mut frame).unwrap();
+        xids += frame.xid as u64 + frame.options.len() as u64;
+    }
+
+    let reused = (start.elapsed(), allocations() - before);
+    let start = Instant::now();
+    let before = allocations();
+
+    for packet in &packets {
+        let frame = FrameRef::parse(packet).unwrap();
+        xids += frame.xid() as u64 + frame.options().count() as u64;
+    }
+
+    let borrowed = (start.elapsed(), allocations() - before);
+
+    println!("Frame::parse      {} packets in {:?}, {} allocations", PACKETS, owned.0, owned.1);
+    println!("Frame::parse_into {} packets in {:?}, {} allocations", PACKETS, reused.0, reused.1);
+    println!("FrameRef::parse   {} packets in {:?}, {} allocations", PACKETS, borrowed.0, borrowed.1);
+    println!("(checksum {})", xids);
+}
@@ -0,0 +1,56 @@
+/*
+ * Feed arbitrary bytes to the frame parsers and to the option decoders of
+ * whatever they accept: every failure must be an Err, never a panic
+ *
+ * Run with `cargo fuzz run frame_parse` from the repository root
+ */
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate dhcp;
+
+use dhcp::common::Frame;
+use dhcp::frame_ref::FrameRef;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(frame) = Frame::parse(data) {
+        decode_all(&frame);
+        let _ = frame.to_bytes();
+    }
+
+    if let Ok(frame) = Frame::parse_bootp(data) {
+        decode_all(&frame);
+    }
+
+    if let Ok(frame) = FrameRef::parse(data) {
+        for _ in frame.options() {}
+    }
+});
+
+/*
+ * Run every typed decoder, their results do not matter
+ */
+fn decode_all(frame: &Frame) {
+    let _ = frame.server_name();
+    let _ = frame.boot_file();
+    let _ = frame.message_type();
+    let _ = frame.dhcp_state();
+    let _ = frame.requested_parameters();
+    let _ = frame.subnet_mask();
+    let _ = frame.routers();
+    let _ = frame.dns_servers();
+    let _ = frame.domain_name();
+    let _ = frame.host_name();
+    let _ = frame.client_fqdn();
+    let _ = frame.domain_search();
+    let _ = frame.classless_routes();
+    let _ = frame.user_class();
+    let _ = frame.vi_vendor_class();
+    let _ = frame.vi_vendor_specific();
+    let _ = frame.relay_agent_info();
+    let _ = frame.client_identifier_parts();
+    let _ = frame.client_key();
+    let _ = frame.max_message_size();
+}
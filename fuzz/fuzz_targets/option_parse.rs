@@ -0,0 +1,31 @@
+/*
+ * Feed arbitrary bytes to the option parsers: every failure must be an Err,
+ * never a panic, and a parsed option never claims more bytes than given
+ *
+ * Run with `cargo fuzz run option_parse` from the repository root
+ */
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate dhcp;
+
+use dhcp::common::Option;
+use dhcp::parser::parse_options;
+use dhcp::writer::options_to_bytes;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((opt, consumed)) = Option::parse(data) {
+        assert!(consumed <= data.len());
+
+        let _ = opt.value_as_string();
+        let _ = opt.as_u32();
+        let _ = opt.as_ipv4();
+    }
+
+    // What was parsed serializes back to something parsing the same
+    if let Ok(opts) = parse_options(data) {
+        assert_eq!(parse_options(&options_to_bytes(&opts)).unwrap(), opts);
+    }
+});
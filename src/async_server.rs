@@ -0,0 +1,165 @@
+/*
+ * DHCP server on a tokio socket, sharing the request handling of the
+ * blocking server
+ */
+
+use std::future::Future;
+use std::net::{self, SocketAddr};
+use std::option;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::ReadBuf;
+use tokio::net::UdpSocket;
+
+use common::{Result, Error, ErrorKind};
+use pool::AddressPool;
+use lease::LeaseStore;
+use server::{self, ServerConfig};
+
+/*
+ * Future answering DHCP requests forever, see serve_async
+ */
+pub struct AsyncServer<P> {
+    socket: UdpSocket,
+    config: ServerConfig,
+    pool: P,
+    leases: LeaseStore,
+    buf: Vec<u8>,
+    reply: option::Option<(Vec<u8>, SocketAddr)> // Response waiting for the socket to be writable
+}
+
+/*
+ * Bind to the configured address and answer DHCP requests when the returned
+ * future is polled. Must be called within a tokio runtime.
+ */
+pub fn listen_async<P: AddressPool + Unpin>(config: ServerConfig, pool: P) -> Result<AsyncServer<P>> {
+    let socket = from_std(server::bind_config(&config)?)?;
+    serve_async(socket, &config, pool)
+}
+
+/*
+ * Convert a blocking socket for use with serve_async
+ */
+pub fn from_std(socket: net::UdpSocket) -> Result<UdpSocket> {
+    socket.set_nonblocking(true)?;
+    Ok(UdpSocket::from_std(socket)?)
+}
+
+/*
+ * Answer DHCP requests received on an already bound tokio socket. Without
+ * IP_PKTINFO the server identifier is the configured server_ip or the bound
+ * address, so binding to all interfaces requires server_ip. Conflict
+ * detection (probe_timeout) is not supported: its ICMP probes block.
+ */
+pub fn serve_async<P: AddressPool + Unpin>(socket: UdpSocket, config: &ServerConfig, pool: P) -> Result<AsyncServer<P>> {
+    if config.probe_timeout.is_some() {
+        return Err(Error::with_kind(ErrorKind::InvalidValue, "probe_timeout is not supported by the async server"));
+    }
+
+    let config = server::local_config(config, socket.local_addr()?, false)?;
+
+    Ok(AsyncServer {
+        socket,
+        buf: vec![0; config.recv_buffer_size],
+        config,
+        pool,
+        leases: LeaseStore::new(),
+        reply: None
+    })
+}
+
+impl<P: AddressPool + Unpin> Future for AsyncServer<P> {
+    type Output = Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some((ref bytes, dst)) = this.reply {
+                match this.socket.poll_send_to(cx, bytes, dst) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Err(e)) => error!("Failed to send response: {}", e),
+                    Poll::Ready(Ok(_)) => {}
+                }
+            }
+
+            this.reply = None;
+
+            let mut buf = ReadBuf::new(&mut this.buf);
+
+            let src = match this.socket.poll_recv_from(cx, &mut buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Ready(Ok(src)) => src
+            };
+
+            let len = buf.filled().len();
+
+            if len == this.buf.len() {
+                warn!("Dropping frame from {}: possibly truncated to {} bytes", src, len);
+                continue;
+            }
+
+            this.reply = server::respond(&this.buf[..len], src, &this.config, this.config.server_ip, &mut this.pool, &mut this.leases)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{UdpSocket, Ipv4Addr};
+    use std::time::Duration;
+
+    use tokio::runtime::Runtime;
+
+    use common::{Frame, MessageType, Opcode};
+    use writer::FrameBuilder;
+    use pool::RangePool;
+    use server::{ServerConfig, ServerConfigBuilder};
+
+    #[test]
+    fn test_async_discover_offer() {
+        let runtime = Runtime::new().unwrap();
+        let _guard = runtime.enter();
+
+        let socket = super::from_std(UdpSocket::bind("127.0.0.1:0").unwrap()).unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        let pool = RangePool::new(Ipv4Addr::new(127, 0, 0, 10), Ipv4Addr::new(127, 0, 0, 20));
+
+        runtime.spawn(super::serve_async(socket, &ServerConfig::default(), pool).unwrap());
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let discover = FrameBuilder::new(Opcode::Request, 0x6e86444c)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+
+        client.send_to(&discover.to_bytes().unwrap(), server_addr).unwrap();
+
+        let mut buf = [0; 1024];
+        let (len, _) = client.recv_from(&mut buf).unwrap();
+        let offer = Frame::parse(&buf[..len]).unwrap();
+
+        assert_eq!(offer.xid, 0x6e86444c);
+        assert_eq!(offer.message_type(), Some(MessageType::Offer));
+        assert_eq!(offer.your_ip(), Ipv4Addr::new(127, 0, 0, 10));
+        assert_eq!(offer.server_identifier(), Some(Ipv4Addr::new(127, 0, 0, 1)));
+    }
+
+    #[test]
+    fn test_async_rejects_probing() {
+        let runtime = Runtime::new().unwrap();
+        let _guard = runtime.enter();
+
+        let socket = super::from_std(UdpSocket::bind("127.0.0.1:0").unwrap()).unwrap();
+        let config = ServerConfigBuilder::new().probe_timeout(Duration::from_millis(100)).build();
+        let pool = RangePool::new(Ipv4Addr::new(127, 0, 0, 10), Ipv4Addr::new(127, 0, 0, 20));
+
+        let e = super::serve_async(socket, &config, pool).err().unwrap();
+        assert_eq!(e.kind(), ::common::ErrorKind::InvalidValue);
+    }
+}
@@ -0,0 +1,330 @@
+/*
+ * DHCP client
+ */
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::net::{UdpSocket, SocketAddr, Ipv4Addr};
+use std::option;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use common::{Result, Error, ErrorKind, Option, Frame, MessageType, Opcode};
+use codes::{OPTION_ADDRESS_REQUEST, OPTION_DHCP_SERVER_ID};
+use lease::Lease;
+use writer::FrameBuilder;
+
+/*
+ * How many times a message is sent before giving up
+ */
+const ATTEMPTS: usize = 2;
+
+/*
+ * Lease time assumed when the server does not send one, in seconds
+ */
+const FALLBACK_LEASE_TIME: u32 = 3600;
+
+/*
+ * Acquires an address through the DISCOVER, OFFER, REQUEST, ACK exchange
+ */
+pub struct Client {
+    mac:         [u8; 6],
+    bind_addr:   SocketAddr, // Local address to receive responses on
+    server_addr: SocketAddr, // Where requests are sent
+    timeout:     Duration    // How long to wait for each response
+}
+
+impl Client {
+    /*
+     * Construct a client using the standard ports, broadcasting its requests
+     */
+    pub fn new(mac: [u8; 6]) -> Client {
+        Client {
+            mac,
+            bind_addr: SocketAddr::from((Ipv4Addr::new(0, 0, 0, 0), 68)),
+            server_addr: SocketAddr::from((Ipv4Addr::new(255, 255, 255, 255), 67)),
+            timeout: Duration::from_secs(4)
+        }
+    }
+
+    /*
+     * Set the local address responses are received on
+     */
+    pub fn set_bind_addr(&mut self, addr: SocketAddr) {
+        self.bind_addr = addr;
+    }
+
+    /*
+     * Set the address requests are sent to
+     */
+    pub fn set_server_addr(&mut self, addr: SocketAddr) {
+        self.server_addr = addr;
+    }
+
+    /*
+     * Set how long to wait for each response before retransmitting
+     */
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /*
+     * Run a full exchange with a server and return the acquired lease
+     */
+    pub fn acquire(&mut self) -> Result<Lease> {
+        let socket = UdpSocket::bind(self.bind_addr)?;
+        socket.set_broadcast(true)?;
+
+        let xid = random_xid();
+
+        // DISCOVER, wait for an OFFER
+        let discover = FrameBuilder::new(Opcode::Request, xid)
+            .client_mac(&self.mac)
+            .message_type(MessageType::Discover)
+            .build()?;
+
+        let offer = self.exchange(&socket, &discover)?;
+        if offer.message_type() != Some(MessageType::Offer) {
+            return Err(Error::new("Expected an offer from the server"));
+        }
+
+        let server_id = match offer.option(OPTION_DHCP_SERVER_ID) {
+            Some(opt) => opt.data.clone(),
+            None => return Err(Error::with_kind(ErrorKind::InvalidOption, "Offer without server identifier"))
+        };
+
+        // REQUEST the offered address from the server that offered it
+        let mut requested = Option::new(OPTION_ADDRESS_REQUEST);
+        requested.set_data(offer.yiaddr.clone())?;
+
+        let mut server = Option::new(OPTION_DHCP_SERVER_ID);
+        server.set_data(server_id)?;
+
+        let request = FrameBuilder::new(Opcode::Request, xid)
+            .client_mac(&self.mac)
+            .message_type(MessageType::Request)
+            .option(requested)
+            .option(server)
+            .build()?;
+
+        let ack = self.exchange(&socket, &request)?;
+        match ack.message_type() {
+            Some(MessageType::Ack) => {},
+            Some(MessageType::Nak) => return Err(Error::new("Request refused by the server")),
+            _ => return Err(Error::new("Expected an ack from the server"))
+        }
+
+        let lease_time = ack.lease_time().unwrap_or(FALLBACK_LEASE_TIME);
+
+        Ok(Lease {
+            mac: self.mac.to_vec(),
+            ip: ack.your_ip(),
+            expires_at: Instant::now() + Duration::from_secs(lease_time as u64)
+        })
+    }
+
+    /*
+     * Send a frame and wait for the matching response, retransmitting once
+     */
+    fn exchange(&self, socket: &UdpSocket, frame: &Frame) -> Result<Frame> {
+        let bytes = frame.to_bytes()?;
+
+        for _ in 0..ATTEMPTS {
+            socket.send_to(&bytes, self.server_addr)?;
+
+            if let Some(reply) = self.receive(socket, frame.xid())? {
+                return Ok(reply);
+            }
+        }
+
+        Err(Error::with_kind(ErrorKind::Timeout, "No response from server"))
+    }
+
+    /*
+     * Wait for a response to a transaction, None on timeout. Frames for
+     * other transactions do not extend the wait.
+     */
+    fn receive(&self, socket: &UdpSocket, xid: u32) -> Result<option::Option<Frame>> {
+        let mut buf = [0; 1500];
+        let deadline = Instant::now() + self.timeout;
+
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left == Duration::from_secs(0) {
+                return Ok(None);
+            }
+
+            socket.set_read_timeout(Some(left))?;
+
+            let len = match socket.recv_from(&mut buf) {
+                Ok((len, _)) => len,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => return Ok(None),
+                Err(e) => return Err(Error::from(e))
+            };
+
+            // Ignore anything that isn't a reply to this transaction
+            match Frame::parse(&buf[..len]) {
+                Ok(reply) => {
+                    if reply.opcode() == Opcode::Reply && reply.xid() == xid && reply.chaddr.starts_with(&self.mac) {
+                        return Ok(Some(reply));
+                    }
+                },
+                Err(_) => continue
+            }
+        }
+    }
+}
+
+/*
+ * Pick a transaction ID for a new exchange. The std hasher is randomly
+ * keyed, a counter and the time make every call differ.
+ */
+pub fn random_xid() -> u32 {
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(CALLS.fetch_add(1, Ordering::Relaxed));
+
+    if let Ok(d) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u32(d.subsec_nanos());
+    }
+
+    hasher.finish() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{UdpSocket, Ipv4Addr};
+    use std::time::{Duration, Instant};
+
+    use common::{Frame, MessageType, Opcode};
+    use pool::RangePool;
+    use server;
+    use writer::FrameBuilder;
+    use super::Client;
+
+    #[test]
+    fn test_client_acquire() {
+        let config = server::ServerConfigBuilder::new()
+            .bind_addr("127.0.0.1:0".parse().unwrap())
+            .build();
+
+        let server = server::Server::start(&config, RangePool::new(Ipv4Addr::new(127, 0, 0, 100), Ipv4Addr::new(127, 0, 0, 110))).unwrap();
+        let server_addr = server.local_addr();
+
+        let mut client = Client::new([0x52, 0x54, 0x01, 0x12, 0x34, 0x56]);
+        client.set_bind_addr("127.0.0.1:0".parse().unwrap());
+        client.set_server_addr(server_addr);
+        client.set_timeout(Duration::from_secs(2));
+
+        let lease = client.acquire().unwrap();
+
+        assert_eq!(lease.ip, Ipv4Addr::new(127, 0, 0, 100));
+        assert_eq!(lease.mac, [0x52, 0x54, 0x01, 0x12, 0x34, 0x56]);
+        assert!(!lease.is_expired(Instant::now()));
+
+        server.stop().unwrap();
+    }
+
+    #[test]
+    fn test_client_timeout() {
+        // A server that never answers
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        let mut client = Client::new([0x52, 0x54, 0x01, 0x12, 0x34, 0x57]);
+        client.set_bind_addr("127.0.0.1:0".parse().unwrap());
+        client.set_server_addr(socket.local_addr().unwrap());
+        client.set_timeout(Duration::from_millis(50));
+
+        let e = client.acquire().err().unwrap();
+        assert_eq!(e.kind(), ::common::ErrorKind::Timeout);
+
+        // The DISCOVER was sent twice
+        let mut buf = [0; 1500];
+        socket.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        assert!(socket.recv_from(&mut buf).is_ok());
+        assert!(socket.recv_from(&mut buf).is_ok());
+    }
+
+    #[test]
+    fn test_random_xid() {
+        assert_ne!(super::random_xid(), super::random_xid());
+    }
+
+    #[test]
+    fn test_client_ignores_other_xid() {
+        // A server answering with the wrong transaction ID
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        let mut client = Client::new([0x52, 0x54, 0x01, 0x12, 0x34, 0x58]);
+        client.set_bind_addr("127.0.0.1:0".parse().unwrap());
+        client.set_server_addr(socket.local_addr().unwrap());
+        client.set_timeout(Duration::from_millis(200));
+
+        let server = ::std::thread::spawn(move || {
+            let mut buf = [0; 1500];
+            let (len, src) = socket.recv_from(&mut buf).unwrap();
+            let discover = Frame::parse(&buf[..len]).unwrap();
+
+            let offer = FrameBuilder::new(Opcode::Reply, discover.xid().wrapping_add(1))
+                .client_mac(discover.hardware_address())
+                .message_type(MessageType::Offer)
+                .build()
+                .unwrap();
+
+            socket.send_to(&offer.to_bytes().unwrap(), src).unwrap();
+        });
+
+        let e = client.acquire().err().unwrap();
+        assert_eq!(e.kind(), ::common::ErrorKind::Timeout);
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_client_busy_lan() {
+        // Replies to other clients keep arriving but must not hold off the
+        // retransmission and the timeout
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+
+        let mut client = Client::new([0x52, 0x54, 0x01, 0x12, 0x34, 0x59]);
+        client.set_bind_addr("127.0.0.1:0".parse().unwrap());
+        client.set_server_addr(socket.local_addr().unwrap());
+        client.set_timeout(Duration::from_millis(100));
+
+        let server = ::std::thread::spawn(move || {
+            let mut buf = [0; 1500];
+            let mut discovers = 0;
+            let mut client = None;
+            let started = Instant::now();
+
+            while discovers < 2 && started.elapsed() < Duration::from_secs(2) {
+                if let Ok((_, src)) = socket.recv_from(&mut buf) {
+                    discovers += 1;
+                    client = Some(src);
+                }
+
+                if let Some(src) = client {
+                    let offer = FrameBuilder::new(Opcode::Reply, 42)
+                        .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x60])
+                        .message_type(MessageType::Offer)
+                        .build()
+                        .unwrap();
+
+                    let _ = socket.send_to(&offer.to_bytes().unwrap(), src);
+                }
+            }
+
+            discovers
+        });
+
+        let started = Instant::now();
+        let e = client.acquire().err().unwrap();
+
+        assert_eq!(e.kind(), ::common::ErrorKind::Timeout);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(server.join().unwrap(), 2);
+    }
+}
@@ -0,0 +1,138 @@
+/*
+ * Framing of DHCP messages carried over a byte stream such as TCP, each
+ * prefixed with its length as two big endian bytes (RFC 6926 section 6.1)
+ */
+
+use std::option;
+
+use byteorder::{BigEndian, ByteOrder};
+
+use common::{Result, Error, ErrorKind, Frame};
+
+/*
+ * Size of the length prefix
+ */
+const PREFIX_LEN: usize = 2;
+
+/*
+ * Splits a byte stream into frames, keeping incomplete data until the rest
+ * arrives
+ */
+#[derive(Default)]
+pub struct FrameCodec {
+    buf: Vec<u8> // Received bytes not yet decoded
+}
+
+impl FrameCodec {
+    /*
+     * Construct a codec with nothing buffered
+     */
+    pub fn new() -> FrameCodec {
+        FrameCodec::default()
+    }
+
+    /*
+     * Append bytes read from the stream
+     */
+    pub fn extend(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /*
+     * Decode the next complete frame, None until one is fully buffered. A
+     * frame that fails to parse is consumed so the following ones can still
+     * be decoded.
+     */
+    pub fn decode(&mut self) -> Result<option::Option<Frame>> {
+        if self.buf.len() < PREFIX_LEN {
+            return Ok(None);
+        }
+
+        let len = BigEndian::read_u16(&self.buf) as usize;
+
+        if self.buf.len() < PREFIX_LEN + len {
+            return Ok(None);
+        }
+
+        let rest = self.buf.split_off(PREFIX_LEN + len);
+        let data = ::std::mem::replace(&mut self.buf, rest);
+
+        Frame::parse(&data[PREFIX_LEN..]).map(Some)
+    }
+
+    /*
+     * Number of buffered bytes not yet decoded
+     */
+    pub fn remaining(&self) -> usize {
+        self.buf.len()
+    }
+
+    /*
+     * Serialize a frame with its length prefix
+     */
+    pub fn encode(frame: &Frame) -> Result<Vec<u8>> {
+        let bytes = frame.to_bytes()?;
+
+        if bytes.len() > u16::MAX as usize {
+            return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Frame too large for the length prefix ({} bytes)", bytes.len())));
+        }
+
+        let mut buf = vec![0; PREFIX_LEN];
+        BigEndian::write_u16(&mut buf, bytes.len() as u16);
+        buf.extend(bytes);
+
+        Ok(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{MessageType, Opcode};
+    use writer::FrameBuilder;
+    use super::FrameCodec;
+
+    fn encoded(xid: u32) -> Vec<u8> {
+        let frame = FrameBuilder::new(Opcode::Request, xid)
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+
+        FrameCodec::encode(&frame).unwrap()
+    }
+
+    #[test]
+    fn test_codec_split() {
+        let data = encoded(1);
+        let mut codec = FrameCodec::new();
+
+        codec.extend(&data[..1]);
+        assert!(codec.decode().unwrap().is_none());
+
+        codec.extend(&data[1..100]);
+        assert!(codec.decode().unwrap().is_none());
+        assert_eq!(codec.remaining(), 100);
+
+        codec.extend(&data[100..]);
+        assert_eq!(codec.decode().unwrap().unwrap().xid, 1);
+        assert_eq!(codec.remaining(), 0);
+    }
+
+    #[test]
+    fn test_codec_coalesced() {
+        let mut data = encoded(1);
+        data.extend(encoded(2));
+        data.extend(vec![0x00, 0x01, 0xff]);
+        data.extend(&encoded(3)[..10]);
+
+        let mut codec = FrameCodec::new();
+        codec.extend(&data);
+
+        assert_eq!(codec.decode().unwrap().unwrap().xid, 1);
+        assert_eq!(codec.decode().unwrap().unwrap().xid, 2);
+
+        // A broken frame is skipped, the partial one kept
+        assert!(codec.decode().is_err());
+        assert!(codec.decode().unwrap().is_none());
+        assert_eq!(codec.remaining(), 10);
+    }
+}
@@ -2,14 +2,41 @@
  * BOOTP/DHCP Frame/Option codes
  */
 
+use std::borrow::Cow;
+use std::option;
+
+pub const DHCP_MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+
+pub const BOOTP_MIN_LEN: usize = 300; // Minimum BOOTP message size accepted by relays
+pub const DHCP_MIN_MAX_MSG_SIZE: u16 = 576; // Smallest maximum message size a client may advertise (RFC 2132 section 9.10)
+
 pub const BOOTP_REQUEST:  u8 = 0x01;
 pub const BOOTP_RESPONSE: u8 = 0x02;
 
+/*
+ * ARP hardware types (htype field), from the IANA registry
+ */
+pub const HTYPE_ETHERNET:      u8 = 1;
+pub const HTYPE_IEEE802:       u8 = 6;
+pub const HTYPE_ARCNET:        u8 = 7;
+pub const HTYPE_FRAME_RELAY:   u8 = 15;
+pub const HTYPE_ATM:           u8 = 16;
+pub const HTYPE_HDLC:          u8 = 17;
+pub const HTYPE_FIBRE_CHANNEL: u8 = 18;
+pub const HTYPE_SERIAL:        u8 = 20;
+pub const HTYPE_INFINIBAND:    u8 = 32;
+
+pub const BOOTP_FLAG_BROADCAST: u16 = 0x8000; // Client cannot receive unicast before configuration
+pub const BOOTP_FLAGS_RESERVED: u16 = 0x7fff; // Must be zero (RFC 2131 section 2)
+
 pub const DHCP_DISCOVER: u8 = 1;
 pub const DHCP_OFFER:    u8 = 2;
 pub const DHCP_REQUEST:  u8 = 3;
+pub const DHCP_DECLINE:  u8 = 4;
 pub const DHCP_ACK:      u8 = 5;
 pub const DHCP_NACK:     u8 = 6;
+pub const DHCP_RELEASE:  u8 = 7;
+pub const DHCP_INFORM:   u8 = 8;
 
 pub const OPTION_PAD:                u8 = 0;
 pub const OPTION_SUBNET_MASK:        u8 = 1;
@@ -23,6 +50,8 @@ pub const OPTION_QUOTES_SERVER:      u8 = 8;
 pub const OPTION_LPR_SERVER:         u8 = 9;
 pub const OPTION_IMPRESS_SERVER:     u8 = 10;
 pub const OPTION_RLP_SERVER:         u8 = 11;
+pub const OPTION_HOST_NAME:          u8 = 12;
+pub const OPTION_BOOT_FILE_SIZE:     u8 = 13;
 pub const OPTION_MERIT_DUMP:         u8 = 14;
 pub const OPTION_DOMAIN_NAME:        u8 = 15;
 pub const OPTION_SWAP_SERVER:        u8 = 16;
@@ -87,4 +116,156 @@ pub const OPTION_IRC_SERVER:         u8 = 74;
 pub const OPTION_STREETTALK_SERVER:  u8 = 75;
 pub const OPTION_STDA_SERVER:        u8 = 76;
 pub const OPTION_USER_CLASS:         u8 = 77;
+pub const OPTION_CLIENT_FQDN:        u8 = 81;
+pub const OPTION_RELAY_AGENT_INFO:   u8 = 82;
+pub const OPTION_CLIENT_ARCH:        u8 = 93;
+pub const OPTION_CLIENT_NDI:         u8 = 94;
+pub const OPTION_CLIENT_UUID:        u8 = 97;
+pub const OPTION_SUBNET_SELECTION:   u8 = 118;
+pub const OPTION_DOMAIN_SEARCH:      u8 = 119;
+pub const OPTION_CLASSLESS_ROUTE:    u8 = 121;
+pub const OPTION_VI_VENDOR_CLASS:    u8 = 124;
+pub const OPTION_VI_VENDOR_SPECIFIC: u8 = 125;
 pub const OPTION_END:                u8 = 255;
+
+/*
+ * Relay Agent Information sub-options (RFC 3046)
+ */
+pub const RELAY_AGENT_CIRCUIT_ID: u8 = 1;
+pub const RELAY_AGENT_REMOTE_ID:  u8 = 2;
+
+/*
+ * Client system architecture types (option 93), from the IANA registry
+ */
+pub const ARCH_X86_BIOS:      u16 = 0;
+pub const ARCH_EFI_IA32:      u16 = 6;
+pub const ARCH_EFI_X86_64:    u16 = 7;
+pub const ARCH_EFI_XSCALE:    u16 = 8;
+pub const ARCH_EFI_BYTE_CODE: u16 = 9;
+pub const ARCH_EFI_ARM32:     u16 = 10;
+pub const ARCH_EFI_ARM64:     u16 = 11;
+
+/*
+ * Human readable name of an option tag, "Unknown (N)" if not recognized
+ */
+pub fn option_name(tag: u8) -> Cow<'static, str> {
+    match known_option_name(tag) {
+        Some(name) => Cow::Borrowed(name),
+        None => Cow::Owned(format!("Unknown ({})", tag))
+    }
+}
+
+/*
+ * Name of an option tag defined by RFC 2132, RFC 3011, RFC 3046, RFC 3397,
+ * RFC 3442, RFC 3925, RFC 4578 or RFC 4702
+ */
+pub fn known_option_name(tag: u8) -> option::Option<&'static str> {
+    match tag {
+        OPTION_PAD                => Some("Pad"),
+        OPTION_SUBNET_MASK        => Some("Subnet Mask"),
+        OPTION_TIME_OFFSET        => Some("Time Offset"),
+        OPTION_ROUTER             => Some("Router"),
+        OPTION_TIME_SERVER        => Some("Time Server"),
+        OPTION_NAME_SERVER        => Some("Name Server"),
+        OPTION_DOMAIN_SERVER      => Some("Domain Name Server"),
+        OPTION_LOG_SERVER         => Some("Log Server"),
+        OPTION_QUOTES_SERVER      => Some("Quotes Server"),
+        OPTION_LPR_SERVER         => Some("LPR Server"),
+        OPTION_IMPRESS_SERVER     => Some("Impress Server"),
+        OPTION_RLP_SERVER         => Some("Resource Location Server"),
+        OPTION_HOST_NAME          => Some("Host Name"),
+        OPTION_BOOT_FILE_SIZE     => Some("Boot File Size"),
+        OPTION_MERIT_DUMP         => Some("Merit Dump File"),
+        OPTION_DOMAIN_NAME        => Some("Domain Name"),
+        OPTION_SWAP_SERVER        => Some("Swap Server"),
+        OPTION_ROOT_PATH          => Some("Root Path"),
+        OPTION_EXTENSION_FILE     => Some("Extensions Path"),
+        OPTION_FORWARD_ON_OFF     => Some("IP Forwarding"),
+        OPTION_SRCRTE_ON_OFF      => Some("Non-Local Source Routing"),
+        OPTION_POLICY_FILTER      => Some("Policy Filter"),
+        OPTION_MAX_DG             => Some("Maximum Datagram Reassembly Size"),
+        OPTION_DEFAULT_IP_TTL     => Some("Default IP Time-to-live"),
+        OPTION_MTU_TIMEOUT        => Some("Path MTU Aging Timeout"),
+        OPTION_MTU_PLATEAU        => Some("Path MTU Plateau Table"),
+        OPTION_MTU_INTERFACE      => Some("Interface MTU"),
+        OPTION_MTU_SUBNET         => Some("All Subnets are Local"),
+        OPTION_BROADCAST_ADDRESS  => Some("Broadcast Address"),
+        OPTION_MASK_DISCOVERY     => Some("Perform Mask Discovery"),
+        OPTION_MASK_SUPPLIER      => Some("Mask Supplier"),
+        OPTION_ROUTER_DISCOVERY   => Some("Perform Router Discovery"),
+        OPTION_ROUTER_REQUEST     => Some("Router Solicitation Address"),
+        OPTION_STATIC_ROUTE       => Some("Static Route"),
+        OPTION_TRAILERS           => Some("Trailer Encapsulation"),
+        OPTION_ARP_TIMEOUT        => Some("ARP Cache Timeout"),
+        OPTION_ETHERNET           => Some("Ethernet Encapsulation"),
+        OPTION_DEFAULT_TCP_TTL    => Some("TCP Default TTL"),
+        OPTION_KEEPALIVE_TIME     => Some("TCP Keepalive Interval"),
+        OPTION_KEEPALIVE_DATA     => Some("TCP Keepalive Garbage"),
+        OPTION_NIS_DOMAIN         => Some("NIS Domain"),
+        OPTION_NIS_SERVERS        => Some("NIS Servers"),
+        OPTION_NTP_SERVERS        => Some("NTP Servers"),
+        OPTION_VENDOR_SPECIFIC    => Some("Vendor Specific Information"),
+        OPTION_NETBIOS_NAME       => Some("NetBIOS Name Server"),
+        OPTION_NETBIOS_DIST       => Some("NetBIOS Datagram Distribution Server"),
+        OPTION_NETBIOS_NODE       => Some("NetBIOS Node Type"),
+        OPTION_NETBIOS_SCOPE      => Some("NetBIOS Scope"),
+        OPTION_X_WINDOW_FONT      => Some("X Window System Font Server"),
+        OPTION_X_WINDOW_MANAGER   => Some("X Window System Display Manager"),
+        OPTION_ADDRESS_REQUEST    => Some("Requested IP Address"),
+        OPTION_ADDRESS_LEASE_TIME => Some("IP Address Lease Time"),
+        OPTION_OVERLOAD           => Some("Option Overload"),
+        OPTION_DHCP_MSG_TYPE      => Some("DHCP Message Type"),
+        OPTION_DHCP_SERVER_ID     => Some("Server Identifier"),
+        OPTION_PARAMETER_LIST     => Some("Parameter Request List"),
+        OPTION_DHCP_MESSAGE       => Some("Message"),
+        OPTION_DHCP_MAX_MSG_SIZE  => Some("Maximum DHCP Message Size"),
+        OPTION_RENEWAL_TIME       => Some("Renewal Time"),
+        OPTION_REBINDING_TIME     => Some("Rebinding Time"),
+        OPTION_CLASS_ID           => Some("Vendor Class Identifier"),
+        OPTION_CLIENT_ID          => Some("Client Identifier"),
+        OPTION_NETWARE_IP_DOMAIN  => Some("NetWare/IP Domain Name"),
+        OPTION_NETWAREIP_OPTION   => Some("NetWare/IP Information"),
+        OPTION_NIS_DOMAIN_NAME    => Some("NIS+ Domain"),
+        OPTION_NIS_SERVER_ADDR    => Some("NIS+ Servers"),
+        OPTION_SERVER_NAME        => Some("TFTP Server Name"),
+        OPTION_BOOTFILE_NAME      => Some("Bootfile Name"),
+        OPTION_HOME_AGENT_ADDRS   => Some("Mobile IP Home Agent"),
+        OPTION_SMTP_SERVER        => Some("SMTP Server"),
+        OPTION_POP3_SERVER        => Some("POP3 Server"),
+        OPTION_NNTP_SERVER        => Some("NNTP Server"),
+        OPTION_WWW_SERVER         => Some("WWW Server"),
+        OPTION_FINGER_SERVER      => Some("Finger Server"),
+        OPTION_IRC_SERVER         => Some("IRC Server"),
+        OPTION_STREETTALK_SERVER  => Some("StreetTalk Server"),
+        OPTION_STDA_SERVER        => Some("StreetTalk Directory Assistance Server"),
+        OPTION_USER_CLASS         => Some("User Class"),
+        OPTION_CLIENT_FQDN        => Some("Client FQDN"),
+        OPTION_RELAY_AGENT_INFO   => Some("Relay Agent Information"),
+        OPTION_CLIENT_ARCH        => Some("Client System Architecture"),
+        OPTION_CLIENT_NDI         => Some("Client Network Interface Identifier"),
+        OPTION_CLIENT_UUID        => Some("Client Machine Identifier"),
+        OPTION_SUBNET_SELECTION   => Some("Subnet Selection"),
+        OPTION_DOMAIN_SEARCH      => Some("Domain Search"),
+        OPTION_CLASSLESS_ROUTE    => Some("Classless Static Route"),
+        OPTION_VI_VENDOR_CLASS    => Some("Vendor-Identifying Vendor Class"),
+        OPTION_VI_VENDOR_SPECIFIC => Some("Vendor-Identifying Vendor-Specific Information"),
+        OPTION_END                => Some("End"),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_option_names() {
+        assert_eq!(super::option_name(1), "Subnet Mask");
+        assert_eq!(super::option_name(53), "DHCP Message Type");
+        assert_eq!(super::option_name(55), "Parameter Request List");
+        assert_eq!(super::option_name(255), "End");
+        assert_eq!(super::option_name(200), "Unknown (200)");
+
+        assert_eq!(super::known_option_name(61), Some("Client Identifier"));
+        assert_eq!(super::known_option_name(200), None);
+        assert_eq!(super::known_option_name(119), Some("Domain Search"));
+    }
+}
@@ -4,22 +4,36 @@
 
 use std::error::Error as StdError;
 use std::result::Result as StdResult;
+use std::option;
 use std::convert::{From, Into};
 use std::fmt::{self, Display, Debug, Formatter};
 use std::io::{self};
+use std::net::Ipv4Addr;
+
+use codes;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+#[cfg(feature = "serde")]
+use serde_fields;
 
 /*
  * Represents a BOOTP/DHCP option
  */
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Option {
     pub tag:  u8,      // Option unique identifier
     pub len:  u8,      // Option length
+    #[cfg_attr(feature = "serde", serde(with = "serde_fields::hex"))]
     pub data: Vec<u8>  // Option data, 'len' bytes of data
 }
 
 /*
  * Represents a complete DHCP frame
  */
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Frame {
     pub op:     u8,      // Opcode
     pub htype:  u8,      // Hardware address type
@@ -28,22 +42,265 @@ pub struct Frame {
     pub xid:    u32,     // Transation ID
     pub secs:   u16,     // Seconds elapsed since the process was initiated
     pub flags:  u16,     // Flags
+    #[cfg_attr(feature = "serde", serde(with = "serde_fields::ipv4"))]
     pub ciaddr: Vec<u8>, // Client IP address (not used in DHCP)
+    #[cfg_attr(feature = "serde", serde(with = "serde_fields::ipv4"))]
     pub yiaddr: Vec<u8>, // Your/client IP address
+    #[cfg_attr(feature = "serde", serde(with = "serde_fields::ipv4"))]
     pub siaddr: Vec<u8>, // Next server IP address
+    #[cfg_attr(feature = "serde", serde(with = "serde_fields::ipv4"))]
     pub giaddr: Vec<u8>, // Relay IP address
+    #[cfg_attr(feature = "serde", serde(with = "serde_fields::hex"))]
     pub chaddr: Vec<u8>, // Client hardware address
+    #[cfg_attr(feature = "serde", serde(with = "serde_fields::hex"))]
     pub sname:  Vec<u8>, // Server hostname
+    #[cfg_attr(feature = "serde", serde(with = "serde_fields::hex"))]
     pub file:   Vec<u8>, // Boot file name, if any
 
     pub options: Vec<Option> // List of BOOTP/DHCP options
 }
 
+/*
+ * Iterate over the options of a frame with `for opt in &frame`
+ */
+impl<'a> IntoIterator for &'a Frame {
+    type Item = &'a Option;
+    type IntoIter = ::std::slice::Iter<'a, Option>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.options.iter()
+    }
+}
+
+/*
+ * Sub-options as code/value pairs, as carried by options 82 and 125
+ */
+pub type SubOptions = Vec<(u8, Vec<u8>)>;
+
+/*
+ * Classless static route as carried by option 121: destination network,
+ * prefix length and router
+ */
+pub type ClasslessRoute = (Ipv4Addr, u8, Ipv4Addr);
+
+/*
+ * DHCP message type, carried by option 53
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MessageType {
+    Discover,
+    Offer,
+    Request,
+    Decline,
+    Ack,
+    Nak,
+    Release,
+    Inform,
+    Unknown(u8) // Any value not defined by RFC 2132
+}
+
+/*
+ * Decode a message type from its option 53 value
+ */
+impl From<u8> for MessageType {
+    fn from(v: u8) -> MessageType {
+        match v {
+            codes::DHCP_DISCOVER => MessageType::Discover,
+            codes::DHCP_OFFER => MessageType::Offer,
+            codes::DHCP_REQUEST => MessageType::Request,
+            codes::DHCP_DECLINE => MessageType::Decline,
+            codes::DHCP_ACK => MessageType::Ack,
+            codes::DHCP_NACK => MessageType::Nak,
+            codes::DHCP_RELEASE => MessageType::Release,
+            codes::DHCP_INFORM => MessageType::Inform,
+            v => MessageType::Unknown(v)
+        }
+    }
+}
+
+/*
+ * Encode a message type as its option 53 value
+ */
+impl From<MessageType> for u8 {
+    fn from(t: MessageType) -> u8 {
+        match t {
+            MessageType::Discover => codes::DHCP_DISCOVER,
+            MessageType::Offer => codes::DHCP_OFFER,
+            MessageType::Request => codes::DHCP_REQUEST,
+            MessageType::Decline => codes::DHCP_DECLINE,
+            MessageType::Ack => codes::DHCP_ACK,
+            MessageType::Nak => codes::DHCP_NACK,
+            MessageType::Release => codes::DHCP_RELEASE,
+            MessageType::Inform => codes::DHCP_INFORM,
+            MessageType::Unknown(v) => v
+        }
+    }
+}
+
+/*
+ * Client state a DHCPREQUEST was sent from (RFC 2131 section 4.3.2), see
+ * Frame::dhcp_state
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RequestState {
+    Selecting,  // Accepting an offer: server identifier and requested IP
+    InitReboot, // Verifying a remembered address: requested IP only
+    Renewing,   // Extending a lease with its server: ciaddr only
+    Rebinding   // Extending a lease with any server: ciaddr only, broadcast
+}
+
+/*
+ * Hardware address type (htype field), ARP hardware type values
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum HardwareType {
+    Ethernet,     // Ethernet and Wi-Fi, 6 byte addresses
+    Ieee802,      // IEEE 802 networks
+    Arcnet,
+    FrameRelay,
+    Atm,
+    Hdlc,
+    FibreChannel,
+    Serial,       // Serial line
+    Infiniband,   // 20 byte addresses, sent in the client identifier (RFC 4390)
+    Other(u8)     // Any other value
+}
+
+impl From<u8> for HardwareType {
+    fn from(v: u8) -> HardwareType {
+        match v {
+            codes::HTYPE_ETHERNET => HardwareType::Ethernet,
+            codes::HTYPE_IEEE802 => HardwareType::Ieee802,
+            codes::HTYPE_ARCNET => HardwareType::Arcnet,
+            codes::HTYPE_FRAME_RELAY => HardwareType::FrameRelay,
+            codes::HTYPE_ATM => HardwareType::Atm,
+            codes::HTYPE_HDLC => HardwareType::Hdlc,
+            codes::HTYPE_FIBRE_CHANNEL => HardwareType::FibreChannel,
+            codes::HTYPE_SERIAL => HardwareType::Serial,
+            codes::HTYPE_INFINIBAND => HardwareType::Infiniband,
+            v => HardwareType::Other(v)
+        }
+    }
+}
+
+impl From<HardwareType> for u8 {
+    fn from(t: HardwareType) -> u8 {
+        match t {
+            HardwareType::Ethernet => codes::HTYPE_ETHERNET,
+            HardwareType::Ieee802 => codes::HTYPE_IEEE802,
+            HardwareType::Arcnet => codes::HTYPE_ARCNET,
+            HardwareType::FrameRelay => codes::HTYPE_FRAME_RELAY,
+            HardwareType::Atm => codes::HTYPE_ATM,
+            HardwareType::Hdlc => codes::HTYPE_HDLC,
+            HardwareType::FibreChannel => codes::HTYPE_FIBRE_CHANNEL,
+            HardwareType::Serial => codes::HTYPE_SERIAL,
+            HardwareType::Infiniband => codes::HTYPE_INFINIBAND,
+            HardwareType::Other(v) => v
+        }
+    }
+}
+
+/*
+ * BOOTP message opcode (op field)
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Opcode {
+    Request,    // BOOTREQUEST, from a client
+    Reply,      // BOOTREPLY, from a server
+    Unknown(u8) // Invalid, rejected by Frame::parse
+}
+
+impl From<u8> for Opcode {
+    fn from(v: u8) -> Opcode {
+        match v {
+            codes::BOOTP_REQUEST => Opcode::Request,
+            codes::BOOTP_RESPONSE => Opcode::Reply,
+            v => Opcode::Unknown(v)
+        }
+    }
+}
+
+impl From<Opcode> for u8 {
+    fn from(op: Opcode) -> u8 {
+        match op {
+            Opcode::Request => codes::BOOTP_REQUEST,
+            Opcode::Reply => codes::BOOTP_RESPONSE,
+            Opcode::Unknown(v) => v
+        }
+    }
+}
+
+/*
+ * The flags field of a frame, see Frame::bootp_flags
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Flags(pub u16);
+
+impl Flags {
+    /*
+     * Whether the client asked for broadcast replies
+     */
+    pub fn broadcast(self) -> bool {
+        self.0 & codes::BOOTP_FLAG_BROADCAST != 0
+    }
+
+    /*
+     * Set or clear the broadcast flag, leaving the other bits untouched
+     */
+    pub fn set_broadcast(&mut self, broadcast: bool) {
+        if broadcast {
+            self.0 |= codes::BOOTP_FLAG_BROADCAST;
+        } else {
+            self.0 &= !codes::BOOTP_FLAG_BROADCAST;
+        }
+    }
+
+    /*
+     * The reserved bits, which should be zero
+     */
+    pub fn reserved(self) -> u16 {
+        self.0 & codes::BOOTP_FLAGS_RESERVED
+    }
+}
+
+impl From<u16> for Flags {
+    fn from(v: u16) -> Flags {
+        Flags(v)
+    }
+}
+
+impl From<Flags> for u16 {
+    fn from(f: Flags) -> u16 {
+        f.0
+    }
+}
+
+/*
+ * Category of an error, for programmatic handling
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorKind {
+    UnexpectedEof,      // The data ended in the middle of a frame or option, more may follow
+    InvalidMagicCookie, // The DHCP magic cookie does not match
+    InvalidOption,      // An option could not be decoded
+    InvalidUtf8,        // String data is not valid UTF-8
+    InvalidValue,       // A value does not fit in its field
+    Io,                 // Underlying I/O error
+    Timeout,            // No response was received in time
+    Other
+}
+
 /*
  * Error type
  */
 pub struct Error {
-    pub msg: String
+    pub msg: String,
+    kind: ErrorKind,
+    source: option::Option<io::Error> // Underlying I/O error, if any
 }
 
 impl Error {
@@ -51,18 +308,47 @@ impl Error {
      * Construct a new Error with a message
      */
     pub fn new<S: Into<String>>(msg: S) -> Error {
+        Error::with_kind(ErrorKind::Other, msg)
+    }
+
+    /*
+     * Construct a new Error of a given kind with a message
+     */
+    pub fn with_kind<S: Into<String>>(kind: ErrorKind, msg: S) -> Error {
         Error {
-            msg: msg.into()
+            msg: msg.into(),
+            kind,
+            source: None
         }
     }
+
+    /*
+     * Get the kind of the error
+     */
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
 }
 
 /*
- * Convert an io::Error to our Error type
+ * Convert an io::Error to our Error type, keeping it as the source
  */
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Error {
-        Error::new(e.description())
+        Error {
+            msg: e.to_string(),
+            kind: ErrorKind::Io,
+            source: Some(e)
+        }
+    }
+}
+
+/*
+ * Standard error trait
+ */
+impl StdError for Error {
+    fn source(&self) -> option::Option<&(dyn StdError + 'static)> {
+        self.source.as_ref().map(|e| e as &(dyn StdError + 'static))
     }
 }
 
@@ -80,7 +366,7 @@ impl Display for Error {
  */
 impl Debug for Error {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "Error {{ msg: {} }}", self.msg)
+        write!(f, "Error {{ kind: {:?}, msg: {} }}", self.kind, self.msg)
     }
 }
 
@@ -88,3 +374,90 @@ impl Debug for Error {
  * Our Result type
  */
 pub type Result<T> = StdResult<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as StdError;
+    use std::io;
+
+    use super::{Error, ErrorKind, Option, Frame, Flags, Opcode};
+
+    #[test]
+    fn test_error_io_source() {
+        let e = Error::from(io::Error::new(io::ErrorKind::UnexpectedEof, "eof"));
+
+        assert_eq!(e.to_string().as_str(), "eof");
+
+        let source = e.source().unwrap();
+        let io = source.downcast_ref::<io::Error>().unwrap();
+
+        assert_eq!(io.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(e.kind(), ErrorKind::Io);
+        assert!(Error::new("no source").source().is_none());
+        assert_eq!(Error::new("no source").kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_error_boxed() {
+        fn fails() -> Result<(), Box<dyn StdError>> {
+            Err(Error::new("boxed"))?
+        }
+
+        assert_eq!(fails().unwrap_err().to_string().as_str(), "boxed");
+    }
+
+    #[test]
+    fn test_flags_broadcast() {
+        let mut frame = Frame::new(Opcode::Request, 42);
+        frame.flags = 0x0001;
+
+        let mut flags = frame.bootp_flags();
+        assert!(!flags.broadcast());
+        assert_eq!(flags.reserved(), 0x0001);
+
+        flags.set_broadcast(true);
+        frame.set_bootp_flags(flags);
+
+        assert!(frame.broadcast());
+        assert_eq!(frame.flags, 0x8001);
+
+        flags.set_broadcast(false);
+        assert_eq!(u16::from(flags), 0x0001);
+        assert_eq!(Flags::from(0x8000).reserved(), 0);
+    }
+
+    #[test]
+    fn test_frame_clone_independent() {
+        let mut original = Frame::new(Opcode::Reply, 42);
+        original.add_option(Option::new(53));
+
+        let mut copy = original.clone();
+        assert_eq!(copy, original);
+
+        copy.xid = 43;
+        copy.yiaddr = vec![10, 0, 0, 1];
+        copy.options[0].set_data_u8(5);
+
+        assert_ne!(copy, original);
+        assert_eq!(original.xid, 42);
+        assert_eq!(original.yiaddr, [0, 0, 0, 0]);
+        assert!(original.options[0].data.is_empty());
+        assert!(format!("{:?}", original).starts_with("Frame { op: 2"));
+    }
+
+    #[test]
+    fn test_frame_into_iterator() {
+        let mut frame = Frame::new(Opcode::Request, 42);
+        frame.add_option(Option::u8(53, 1));
+        frame.add_option(Option::u32(51, 3600));
+        frame.add_option(Option::new(55));
+
+        let mut sum = 0;
+        for opt in &frame {
+            sum += opt.tag as u32;
+        }
+
+        assert_eq!(sum, 53 + 51 + 55);
+        assert_eq!((&frame).into_iter().count(), frame.options.len());
+    }
+}
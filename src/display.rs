@@ -0,0 +1,250 @@
+/*
+ * Human readable rendering of frames, for debugging
+ */
+
+use std::fmt::{self, Display, Formatter};
+use std::net::Ipv4Addr;
+
+use byteorder::{BigEndian, ByteOrder};
+
+use common::{Option, Frame, MessageType, HardwareType};
+use codes::{known_option_name, BOOTP_REQUEST, BOOTP_RESPONSE, OPTION_SUBNET_MASK, OPTION_ROUTER, OPTION_DOMAIN_SERVER, OPTION_HOST_NAME,
+            OPTION_DOMAIN_NAME, OPTION_ADDRESS_REQUEST, OPTION_ADDRESS_LEASE_TIME, OPTION_DHCP_MSG_TYPE,
+            OPTION_DHCP_SERVER_ID, OPTION_PARAMETER_LIST, OPTION_RENEWAL_TIME, OPTION_REBINDING_TIME,
+            OPTION_CLASS_ID};
+
+/*
+ * Message type names, as used by tcpdump
+ */
+impl Display for MessageType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            MessageType::Discover => write!(f, "DHCPDISCOVER"),
+            MessageType::Offer => write!(f, "DHCPOFFER"),
+            MessageType::Request => write!(f, "DHCPREQUEST"),
+            MessageType::Decline => write!(f, "DHCPDECLINE"),
+            MessageType::Ack => write!(f, "DHCPACK"),
+            MessageType::Nak => write!(f, "DHCPNAK"),
+            MessageType::Release => write!(f, "DHCPRELEASE"),
+            MessageType::Inform => write!(f, "DHCPINFORM"),
+            MessageType::Unknown(v) => write!(f, "DHCP message type {}", v)
+        }
+    }
+}
+
+/*
+ * Hardware type name, or its number when unknown
+ */
+impl Display for HardwareType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            HardwareType::Ethernet => write!(f, "Ethernet"),
+            HardwareType::Ieee802 => write!(f, "IEEE 802"),
+            HardwareType::Arcnet => write!(f, "ARCNET"),
+            HardwareType::FrameRelay => write!(f, "Frame Relay"),
+            HardwareType::Atm => write!(f, "ATM"),
+            HardwareType::Hdlc => write!(f, "HDLC"),
+            HardwareType::FibreChannel => write!(f, "Fibre Channel"),
+            HardwareType::Serial => write!(f, "Serial Line"),
+            HardwareType::Infiniband => write!(f, "InfiniBand"),
+            HardwareType::Other(v) => write!(f, "hardware type {}", v)
+        }
+    }
+}
+
+/*
+ * Multi-line summary of a frame: header, addresses and decoded options
+ */
+impl Display for Frame {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let op = match self.op {
+            BOOTP_REQUEST => "BOOTREQUEST".to_string(),
+            BOOTP_RESPONSE => "BOOTREPLY".to_string(),
+            op => format!("op {}", op)
+        };
+
+        write!(f, "{} xid 0x{:08x}", op, self.xid)?;
+
+        if let Some(t) = self.message_type() {
+            write!(f, ", {}", t)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "  client {} ({}), hops {}, secs {}, flags 0x{:04x}", self.client_mac_string(), self.hardware_type(), self.hops, self.secs, self.flags)?;
+        writeln!(f, "  ciaddr {}, yiaddr {}, siaddr {}, giaddr {}", self.client_ip(), self.your_ip(), self.server_ip(), self.relay_ip())?;
+
+        for opt in &self.options {
+            match known_option_name(opt.tag) {
+                Some(name) => write!(f, "  option {} ({}): ", opt.tag, name)?,
+                None => write!(f, "  option {}: ", opt.tag)?
+            }
+
+            write_value(f, opt)?;
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/*
+ * Write an option value decoded according to its tag, hex bytes otherwise
+ */
+fn write_value(f: &mut Formatter, opt: &Option) -> fmt::Result {
+    let data = &opt.data;
+
+    match opt.tag {
+        OPTION_DHCP_MSG_TYPE if data.len() == 1 => write!(f, "{}", MessageType::from(data[0])),
+
+        OPTION_SUBNET_MASK | OPTION_ROUTER | OPTION_DOMAIN_SERVER | OPTION_ADDRESS_REQUEST | OPTION_DHCP_SERVER_ID
+            if !data.is_empty() && data.len().is_multiple_of(4) => {
+            let ips: Vec<String> = data.chunks(4).map(|c| Ipv4Addr::new(c[0], c[1], c[2], c[3]).to_string()).collect();
+            write!(f, "{}", ips.join(", "))
+        },
+
+        OPTION_ADDRESS_LEASE_TIME | OPTION_RENEWAL_TIME | OPTION_REBINDING_TIME if data.len() == 4 => {
+            write!(f, "{}s", BigEndian::read_u32(data))
+        },
+
+        OPTION_PARAMETER_LIST => {
+            let tags: Vec<String> = data.iter().map(|t| t.to_string()).collect();
+            write!(f, "{}", tags.join(", "))
+        },
+
+        OPTION_HOST_NAME | OPTION_DOMAIN_NAME | OPTION_CLASS_ID => match opt.value_as_string() {
+            Ok(s) => write!(f, "\"{}\"", s),
+            Err(_) => write_hex(f, data)
+        },
+
+        _ => write_hex(f, data)
+    }
+}
+
+/*
+ * Write bytes as space separated hex pairs
+ */
+fn write_hex(f: &mut Formatter, data: &[u8]) -> fmt::Result {
+    let hex: Vec<String> = data.iter().map(|b| format!("{:02x}", b)).collect();
+    write!(f, "{}", hex.join(" "))
+}
+
+impl Option {
+    /*
+     * Offset annotated hex and ASCII dump of the serialized option
+     */
+    pub fn hex_dump(&self) -> String {
+        match self.to_bytes() {
+            Ok(bytes) => hex_dump(&bytes),
+            Err(e) => format!("<{}>", e)
+        }
+    }
+}
+
+impl Frame {
+    /*
+     * Offset annotated hex and ASCII dump of the serialized frame
+     */
+    pub fn hex_dump(&self) -> String {
+        match self.to_bytes() {
+            Ok(bytes) => hex_dump(&bytes),
+            Err(e) => format!("<{}>", e)
+        }
+    }
+}
+
+/*
+ * Dump bytes like `hexdump -C`: offset, 16 hex bytes, ASCII
+ */
+fn hex_dump(data: &[u8]) -> String {
+    let mut out = String::new();
+
+    for (i, line) in data.chunks(16).enumerate() {
+        out.push_str(&format!("{:08x} ", i * 16));
+
+        for j in 0..16 {
+            if j == 8 {
+                out.push(' ');
+            }
+
+            match line.get(j) {
+                Some(b) => out.push_str(&format!(" {:02x}", b)),
+                None => out.push_str("   ")
+            }
+        }
+
+        let ascii: String = line.iter()
+            .map(|&b| if (0x20..0x7f).contains(&b) { b as char } else { '.' })
+            .collect();
+
+        out.push_str(&format!("  |{}|\n", ascii));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{Option, MessageType, Opcode};
+    use writer::FrameBuilder;
+
+    #[test]
+    fn test_frame_display_discover() {
+        let mut host = Option::new(12);
+        host.set_data_str("pxe").unwrap();
+
+        let frame = FrameBuilder::new(Opcode::Request, 0x6e86444c)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(MessageType::Discover)
+            .option(Option::parameter_request_list(&[1, 3, 6]).unwrap())
+            .option(host)
+            .build()
+            .unwrap();
+
+        let s = frame.to_string();
+
+        assert!(s.starts_with("BOOTREQUEST xid 0x6e86444c, DHCPDISCOVER\n"));
+        assert!(s.contains("52:54:01:12:34:56 (Ethernet)"));
+        assert!(s.contains("ciaddr 0.0.0.0"));
+        assert!(s.contains("option 53 (DHCP Message Type): DHCPDISCOVER"));
+        assert!(s.contains("option 55 (Parameter Request List): 1, 3, 6"));
+        assert!(s.contains("option 12 (Host Name): \"pxe\""));
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        assert_eq!(super::hex_dump(&[0x63, 0x82, 0x53, 0x63]).as_str(), "00000000  63 82 53 63                                       |c.Sc|\n");
+
+        let mut host = Option::new(12);
+        host.set_data_str("pxe-client-01234").unwrap();
+
+        let dump = host.hex_dump();
+        let lines: Vec<&str> = dump.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("00000000  0c 10 70 78 65 2d 63 6c  69 65 6e 74 2d 30 31 32"));
+        assert!(lines[1].starts_with("00000010  33 34 "));
+        assert!(lines[0].ends_with("|..pxe-client-012|"));
+
+        let frame = FrameBuilder::new(Opcode::Request, 42).build().unwrap();
+        assert_eq!(frame.hex_dump().lines().count(), 16);
+    }
+
+    #[test]
+    fn test_frame_display_values() {
+        let mut frame = FrameBuilder::new(Opcode::Reply, 1)
+            .message_type(MessageType::Ack)
+            .lease_time(86400)
+            .option(Option { tag: 200, len: 2, data: vec![0xbe, 0xef] })
+            .build()
+            .unwrap();
+
+        frame.set_server_identifier("10.0.0.1".parse().unwrap());
+
+        let s = frame.to_string();
+
+        assert!(s.contains("BOOTREPLY"));
+        assert!(s.contains("option 51 (IP Address Lease Time): 86400s"));
+        assert!(s.contains("option 54 (Server Identifier): 10.0.0.1"));
+        assert!(s.contains("option 200: be ef"));
+    }
+}
@@ -0,0 +1,209 @@
+/*
+ * Zero-copy frame parsing, borrowing from the received buffer
+ */
+
+use std::net::Ipv4Addr;
+
+use byteorder::{BigEndian, ByteOrder};
+
+use common::{Result, Error, ErrorKind, MessageType};
+use codes::{DHCP_MAGIC_COOKIE, OPTION_PAD, OPTION_END, OPTION_DHCP_MSG_TYPE};
+
+/*
+ * Offset of the first option, after the fixed header and the magic cookie
+ */
+const OPTIONS_OFFSET: usize = 240;
+
+/*
+ * A DHCP frame borrowing its fields from the input buffer. Use Frame to
+ * modify or build frames. Option overload (52) is not applied.
+ */
+pub struct FrameRef<'a> {
+    buf: &'a [u8]
+}
+
+/*
+ * An option borrowing its data from the input buffer
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct OptionRef<'a> {
+    pub tag:  u8,
+    pub data: &'a [u8]
+}
+
+/*
+ * Iterator over the options of a FrameRef
+ */
+pub struct OptionsRef<'a> {
+    buf: &'a [u8],
+    pos: usize
+}
+
+impl<'a> FrameRef<'a> {
+    /*
+     * Check the structure of a frame without copying anything
+     */
+    pub fn parse(buf: &'a [u8]) -> Result<FrameRef<'a>> {
+        if buf.len() < OPTIONS_OFFSET {
+            return Err(Error::with_kind(ErrorKind::UnexpectedEof, "Frame too short"));
+        }
+
+        if buf[236..240] != DHCP_MAGIC_COOKIE {
+            return Err(Error::with_kind(ErrorKind::InvalidMagicCookie, "Invalid magic cookie"));
+        }
+
+        // Walk the options once so the iterator cannot fail later
+        let mut pos = 0;
+        let options = &buf[OPTIONS_OFFSET..];
+
+        while let Some((_, next)) = next_option(options, pos)? {
+            pos = next;
+        }
+
+        Ok(FrameRef { buf })
+    }
+
+    pub fn op(&self) -> u8 { self.buf[0] }
+    pub fn htype(&self) -> u8 { self.buf[1] }
+    pub fn hlen(&self) -> u8 { self.buf[2] }
+    pub fn hops(&self) -> u8 { self.buf[3] }
+    pub fn xid(&self) -> u32 { BigEndian::read_u32(&self.buf[4..8]) }
+    pub fn secs(&self) -> u16 { BigEndian::read_u16(&self.buf[8..10]) }
+    pub fn flags(&self) -> u16 { BigEndian::read_u16(&self.buf[10..12]) }
+
+    pub fn ciaddr(&self) -> &'a [u8] { &self.buf[12..16] }
+    pub fn yiaddr(&self) -> &'a [u8] { &self.buf[16..20] }
+    pub fn siaddr(&self) -> &'a [u8] { &self.buf[20..24] }
+    pub fn giaddr(&self) -> &'a [u8] { &self.buf[24..28] }
+    pub fn chaddr(&self) -> &'a [u8] { &self.buf[28..44] }
+    pub fn sname(&self) -> &'a [u8] { &self.buf[44..108] }
+    pub fn file(&self) -> &'a [u8] { &self.buf[108..236] }
+
+    /*
+     * Your (client) IP address, as assigned by the server (yiaddr field)
+     */
+    pub fn your_ip(&self) -> Ipv4Addr {
+        let a = self.yiaddr();
+        Ipv4Addr::new(a[0], a[1], a[2], a[3])
+    }
+
+    /*
+     * Iterate over the options
+     */
+    pub fn options(&self) -> OptionsRef<'a> {
+        OptionsRef {
+            buf: &self.buf[OPTIONS_OFFSET..],
+            pos: 0
+        }
+    }
+
+    /*
+     * Get an option
+     */
+    pub fn option(&self, tag: u8) -> Option<OptionRef<'a>> {
+        self.options().find(|opt| opt.tag == tag)
+    }
+
+    /*
+     * Get the DHCP message type (option 53), if present and well formed
+     */
+    pub fn message_type(&self) -> Option<MessageType> {
+        match self.option(OPTION_DHCP_MSG_TYPE) {
+            Some(opt) if opt.data.len() == 1 => Some(MessageType::from(opt.data[0])),
+            _ => None
+        }
+    }
+}
+
+impl<'a> Iterator for OptionsRef<'a> {
+    type Item = OptionRef<'a>;
+
+    fn next(&mut self) -> Option<OptionRef<'a>> {
+        match next_option(self.buf, self.pos) {
+            Ok(Some((opt, next))) => {
+                self.pos = next;
+                Some(opt)
+            },
+            _ => None
+        }
+    }
+}
+
+/*
+ * Decode the option at pos, skipping Pad, and return it with the position of
+ * the next one. None at End or at the end of the buffer.
+ */
+fn next_option(buf: &[u8], mut pos: usize) -> Result<Option<(OptionRef<'_>, usize)>> {
+    while pos < buf.len() && buf[pos] == OPTION_PAD {
+        pos += 1;
+    }
+
+    if pos >= buf.len() || buf[pos] == OPTION_END {
+        return Ok(None);
+    }
+
+    if pos + 2 > buf.len() {
+        return Err(Error::with_kind(ErrorKind::UnexpectedEof, "Failed to parse option: Frame too short"));
+    }
+
+    let tag = buf[pos];
+    let len = buf[pos + 1] as usize;
+    let end = pos + 2 + len;
+
+    if end > buf.len() {
+        return Err(Error::with_kind(ErrorKind::UnexpectedEof, "Failed to parse option: Option length exceeds buffer"));
+    }
+
+    Ok(Some((OptionRef { tag, data: &buf[pos + 2..end] }, end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{Frame, MessageType, Opcode};
+    use writer::FrameBuilder;
+    use super::FrameRef;
+
+    #[test]
+    fn test_frame_ref_matches_frame() {
+        let mut frame = FrameBuilder::new(Opcode::Reply, 0x6e86444c)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(MessageType::Offer)
+            .your_ip("10.0.0.5".parse().unwrap())
+            .lease_time(3600)
+            .build()
+            .unwrap();
+
+        frame.flags = 0x8000;
+
+        let bytes = frame.to_bytes().unwrap();
+        let owned = Frame::parse(&bytes).unwrap();
+        let borrowed = FrameRef::parse(&bytes).unwrap();
+
+        assert_eq!(borrowed.op(), owned.op);
+        assert_eq!(borrowed.xid(), owned.xid);
+        assert_eq!(borrowed.flags(), owned.flags);
+        assert_eq!(borrowed.chaddr(), owned.chaddr.as_slice());
+        assert_eq!(borrowed.yiaddr(), owned.yiaddr.as_slice());
+        assert_eq!(borrowed.your_ip(), owned.your_ip());
+        assert_eq!(borrowed.message_type(), Some(MessageType::Offer));
+        assert_eq!(borrowed.options().count(), owned.options.len());
+        assert_eq!(borrowed.option(51).unwrap().data, owned.option(51).unwrap().data.as_slice());
+        assert!(borrowed.option(1).is_none());
+    }
+
+    #[test]
+    fn test_frame_ref_invalid() {
+        let bytes = FrameBuilder::new(Opcode::Request, 0).build().unwrap().to_bytes().unwrap();
+
+        assert!(FrameRef::parse(&bytes[..100]).is_err());
+
+        let mut bad = bytes.clone();
+        bad[239] = 0;
+        assert!(FrameRef::parse(&bad).is_err());
+
+        // An option running past the end of the buffer
+        let mut truncated = bytes[..240].to_vec();
+        truncated.extend(&[0x35, 0x05, 0x01]);
+        assert!(FrameRef::parse(&truncated).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+/*
+ * Option lookups by binary search, for frames queried many times
+ */
+
+use std::option;
+
+use common::{Frame, Option};
+
+/*
+ * A frame with its options indexed by tag. Frame::option scans every
+ * option, this finds one in O(log n) for the cost of a small sorted list
+ * built once.
+ */
+pub struct IndexedFrame<'a> {
+    frame: &'a Frame,
+    index: Vec<(u8, usize)> // Tag and position of its first instance, sorted by tag
+}
+
+impl Frame {
+    /*
+     * Index the options of the frame for repeated lookups
+     */
+    pub fn indexed(&self) -> IndexedFrame<'_> {
+        let mut index: Vec<(u8, usize)> = self.options.iter()
+            .enumerate()
+            .map(|(i, opt)| (opt.tag, i))
+            .collect();
+
+        // Stable, so the first instance of a tag comes first
+        index.sort_by_key(|&(tag, _)| tag);
+        index.dedup_by_key(|&mut (tag, _)| tag);
+
+        IndexedFrame {
+            frame: self,
+            index
+        }
+    }
+}
+
+impl<'a> IndexedFrame<'a> {
+    /*
+     * Get the first option with a tag, like Frame::option
+     */
+    pub fn option(&self, tag: u8) -> option::Option<&'a Option> {
+        let frame = self.frame;
+
+        self.index.binary_search_by_key(&tag, |&(tag, _)| tag)
+            .ok()
+            .map(|i| &frame.options[self.index[i].1])
+    }
+
+    /*
+     * Check whether an option is present
+     */
+    pub fn has_option(&self, tag: u8) -> bool {
+        self.option(tag).is_some()
+    }
+
+    /*
+     * Get the indexed frame
+     */
+    pub fn frame(&self) -> &'a Frame {
+        self.frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{Frame, Option, Opcode};
+
+    #[test]
+    fn test_indexed_lookup() {
+        let mut frame = Frame::new(Opcode::Request, 0);
+
+        for tag in (1..31).rev() {
+            let mut opt = Option::new(tag);
+            opt.set_data_u8(tag);
+            frame.add_option(opt);
+        }
+
+        let mut dup = Option::new(12);
+        dup.set_data_u8(0);
+        frame.add_option(dup);
+
+        let indexed = frame.indexed();
+
+        for tag in 1..31 {
+            assert_eq!(indexed.option(tag), frame.option(tag));
+        }
+
+        assert_eq!(indexed.option(12).unwrap().data, [12]);
+        assert!(!indexed.has_option(53));
+    }
+}
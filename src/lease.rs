@@ -0,0 +1,187 @@
+/*
+ * Lease tracking
+ */
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::option;
+use std::time::{Duration, Instant};
+
+use pool::AddressPool;
+
+/*
+ * An address bound to a client until an expiry time
+ */
+pub struct Lease {
+    pub mac:        Vec<u8>,  // Client key, identifier or hardware address
+    pub ip:         Ipv4Addr, // Leased address
+    pub expires_at: Instant   // When the address can be reclaimed
+}
+
+impl Lease {
+    /*
+     * Check whether the lease has expired at a given time
+     */
+    pub fn is_expired(&self, now: Instant) -> bool {
+        now >= self.expires_at
+    }
+}
+
+/*
+ * Active leases, keyed by client (see Frame::client_key)
+ */
+#[derive(Default)]
+pub struct LeaseStore {
+    leases: HashMap<Vec<u8>, Lease>
+}
+
+impl LeaseStore {
+    /*
+     * Construct an empty store
+     */
+    pub fn new() -> LeaseStore {
+        LeaseStore {
+            leases: HashMap::new()
+        }
+    }
+
+    /*
+     * Get the lease of a client, if any
+     */
+    pub fn get(&self, mac: &[u8]) -> option::Option<&Lease> {
+        self.leases.get(mac)
+    }
+
+    /*
+     * Record a new lease or extend an existing one
+     */
+    pub fn renew(&mut self, mac: &[u8], ip: Ipv4Addr, duration: Duration) -> &Lease {
+        let lease = Lease {
+            mac: mac.to_vec(),
+            ip,
+            expires_at: Instant::now() + duration
+        };
+
+        self.leases.insert(mac.to_vec(), lease);
+        &self.leases[mac]
+    }
+
+    /*
+     * Hold an offered address for a client until it requests it, unless it
+     * already has a longer lease on that address
+     */
+    pub fn reserve(&mut self, mac: &[u8], ip: Ipv4Addr, duration: Duration) {
+        let expires_at = Instant::now() + duration;
+
+        match self.leases.get(mac) {
+            Some(lease) if lease.ip == ip && lease.expires_at >= expires_at => {},
+            _ => {
+                self.leases.insert(mac.to_vec(), Lease {
+                    mac: mac.to_vec(),
+                    ip,
+                    expires_at
+                });
+            }
+        }
+    }
+
+    /*
+     * Remove the lease of a client
+     */
+    pub fn remove(&mut self, mac: &[u8]) -> option::Option<Lease> {
+        self.leases.remove(mac)
+    }
+
+    /*
+     * Drop the leases expired at a given time and give their addresses back
+     * to the pool, returns how many were reclaimed
+     */
+    pub fn expire(&mut self, pool: &mut impl AddressPool, now: Instant) -> usize {
+        let expired: Vec<Vec<u8>> = self.leases.values()
+            .filter(|lease| lease.is_expired(now))
+            .map(|lease| lease.mac.clone())
+            .collect();
+
+        for mac in &expired {
+            self.leases.remove(mac);
+            pool.release(mac);
+        }
+
+        expired.len()
+    }
+
+    /*
+     * Number of active leases
+     */
+    pub fn len(&self) -> usize {
+        self.leases.len()
+    }
+
+    /*
+     * Check whether there are no active leases
+     */
+    pub fn is_empty(&self) -> bool {
+        self.leases.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::time::{Duration, Instant};
+
+    use pool::{AddressPool, RangePool};
+    use super::LeaseStore;
+
+    #[test]
+    fn test_lease_expiry_reclaims_address() {
+        let ip = Ipv4Addr::new(10, 0, 0, 10);
+        let mut pool = RangePool::new(ip, ip);
+        let mut leases = LeaseStore::new();
+
+        assert_eq!(pool.allocate(&[1]), Some(ip));
+        leases.renew(&[1], ip, Duration::from_millis(10));
+
+        // Still leased, nothing to reclaim
+        assert_eq!(leases.expire(&mut pool, Instant::now()), 0);
+        assert!(pool.allocate(&[2]).is_none());
+
+        let later = Instant::now() + Duration::from_millis(20);
+
+        assert!(leases.get(&[1]).unwrap().is_expired(later));
+        assert_eq!(leases.expire(&mut pool, later), 1);
+        assert!(leases.is_empty());
+        assert_eq!(pool.allocate(&[2]), Some(ip));
+    }
+
+    #[test]
+    fn test_lease_reserve() {
+        let mut leases = LeaseStore::new();
+        let ip = Ipv4Addr::new(10, 0, 0, 10);
+
+        leases.reserve(&[1], ip, Duration::from_secs(60));
+        let offered = leases.get(&[1]).unwrap().expires_at;
+
+        // An offer does not shorten a lease already granted
+        leases.renew(&[2], ip, Duration::from_secs(3600));
+        let leased = leases.get(&[2]).unwrap().expires_at;
+        leases.reserve(&[2], ip, Duration::from_secs(60));
+
+        assert!(offered < leased);
+        assert_eq!(leases.get(&[2]).unwrap().expires_at, leased);
+        assert_eq!(leases.len(), 2);
+    }
+
+    #[test]
+    fn test_lease_renew() {
+        let mut leases = LeaseStore::new();
+        let ip = Ipv4Addr::new(10, 0, 0, 10);
+
+        let first = leases.renew(&[1], ip, Duration::from_secs(10)).expires_at;
+        let second = leases.renew(&[1], ip, Duration::from_secs(3600)).expires_at;
+
+        assert!(second > first);
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases.get(&[1]).unwrap().ip, ip);
+    }
+}
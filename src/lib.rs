@@ -1,6 +1,34 @@
 extern crate byteorder;
+#[macro_use]
+extern crate log;
+extern crate socket2;
+#[cfg(target_os = "linux")]
+extern crate libc;
+
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "tokio")]
+extern crate tokio;
 
 pub mod codes;
 pub mod common;
 pub mod parser;
+pub mod frame_ref;
+pub mod codec;
+pub mod index;
 pub mod writer;
+pub mod pool;
+pub mod probe;
+pub mod lease;
+pub mod server;
+pub mod relay;
+pub mod client;
+#[cfg(feature = "tokio")]
+pub mod async_server;
+pub mod v6;
+
+mod display;
+mod pktinfo;
+
+#[cfg(feature = "serde")]
+mod serde_fields;
@@ -2,186 +2,1433 @@
  * DHCP Frame parsing
  */
 
-use std::error::Error as StdError;
 use std::vec::Vec;
-use std::io::{Cursor, Read};
+use std::io::{self, Cursor, Read};
 use std::option;
+use std::net::Ipv4Addr;
 
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ByteOrder};
 
-use common::{Result, Error, Option, Frame};
+use common::{Result, Error, ErrorKind, Option, Frame, Flags, MessageType, Opcode, HardwareType, RequestState, SubOptions, ClasslessRoute};
+use codes::{DHCP_MAGIC_COOKIE, OPTION_PAD, OPTION_SUBNET_MASK, OPTION_ROUTER, OPTION_DOMAIN_SERVER, OPTION_HOST_NAME, OPTION_DOMAIN_NAME, OPTION_ADDRESS_REQUEST, OPTION_ADDRESS_LEASE_TIME, OPTION_OVERLOAD, OPTION_DHCP_MSG_TYPE, OPTION_DHCP_MAX_MSG_SIZE, OPTION_DHCP_SERVER_ID, OPTION_PARAMETER_LIST, OPTION_RENEWAL_TIME, OPTION_REBINDING_TIME, OPTION_CLIENT_ID, OPTION_RELAY_AGENT_INFO, OPTION_CLIENT_FQDN, OPTION_SUBNET_SELECTION, OPTION_DOMAIN_SEARCH, OPTION_CLASSLESS_ROUTE, OPTION_USER_CLASS, OPTION_VI_VENDOR_CLASS, OPTION_VI_VENDOR_SPECIFIC, OPTION_VENDOR_SPECIFIC, OPTION_CLASS_ID, OPTION_SERVER_NAME, OPTION_BOOTFILE_NAME, OPTION_CLIENT_ARCH, OPTION_CLIENT_NDI, OPTION_CLIENT_UUID, OPTION_END};
 
 impl Option {
     /*
-     * Parse an option from a bytes buffer
+     * Parse an option from the start of a bytes buffer, returning it with
+     * the number of bytes it takes: 2 + len, or 1 for Pad and End
      */
-    pub fn parse(buf: &[u8]) -> Result<Option> {
+    pub fn parse(buf: &[u8]) -> Result<(Option, usize)> {
+        match buf.first() {
+            Some(&tag) if tag == OPTION_PAD || tag == OPTION_END => return Ok((Option { tag, len: 0, data: Vec::new() }, 1)),
+            _ => {}
+        }
+
         if buf.len() < 2 {
-            return Err(Error::new("Frame too short"));
+            return Err(Error::with_kind(ErrorKind::UnexpectedEof, "Frame too short"));
         }
 
         let mut cur = Cursor::new(buf);
 
         // Parse tag & length
         let mut first = [0; 2];
-        try!(cur.read_exact(&mut first));
+        cur.read_exact(&mut first)?;
 
         let tag = first[0];
         let len = first[1];
 
+        if buf.len() < 2 + len as usize {
+            return Err(Error::with_kind(ErrorKind::UnexpectedEof, "Option length exceeds buffer"));
+        }
+
         // Get the data
         let mut data = vec![0; len as usize];
-        try!(cur.read_exact(&mut data));
+        cur.read_exact(&mut data)?;
 
         // Construct the object
-        Ok(Option {
-            tag: tag,
-            len: len,
-            data: data
-        })
+        Ok((Option {
+            tag,
+            len,
+            data
+        }, 2 + len as usize))
+    }
+
+    /*
+     * Get the number of data bytes, which is what gets serialized whatever
+     * the len field says
+     */
+    pub fn data_len(&self) -> usize {
+        self.data.len()
     }
 
     /*
-     * Return the value as a string
+     * Return the value as a string, without trailing NUL bytes
      */
     pub fn value_as_string(&self) -> Result<String> {
-        let data = self.data.clone();
+        bytes_to_string(&self.data)
+    }
+
+    /*
+     * Return the value as a single byte
+     */
+    pub fn as_u8(&self) -> Result<u8> {
+        self.expect_len(1)?;
+        Ok(self.data[0])
+    }
+
+    /*
+     * Return the value as a big endian 16 bit integer
+     */
+    pub fn as_u16(&self) -> Result<u16> {
+        self.expect_len(2)?;
+        Ok(BigEndian::read_u16(&self.data))
+    }
+
+    /*
+     * Return the value as a big endian 32 bit integer
+     */
+    pub fn as_u32(&self) -> Result<u32> {
+        self.expect_len(4)?;
+        Ok(BigEndian::read_u32(&self.data))
+    }
+
+    /*
+     * Return the value as an IPv4 address
+     */
+    pub fn as_ipv4(&self) -> Result<Ipv4Addr> {
+        self.expect_len(4)?;
+        Ok(bytes_to_ipv4(&self.data))
+    }
+
+    /*
+     * Check the data length before decoding a fixed size value
+     */
+    fn expect_len(&self, len: usize) -> Result<()> {
+        if self.data.len() != len {
+            return Err(Error::with_kind(ErrorKind::InvalidOption, format!("Option {} has {} bytes of data, expected {}", self.tag, self.data.len(), len)));
+        }
+
+        Ok(())
+    }
+}
+
+/*
+ * Read the fixed BOOTP header, up to the magic cookie, with no options
+ */
+fn read_header<R: Read>(r: &mut R) -> Result<Frame> {
+    let mut frame = Frame {
+        op: 0,
+        htype: 0,
+        hlen: 0,
+        hops: 0,
+        xid: 0,
+        secs: 0,
+        flags: 0,
+        ciaddr: Vec::new(),
+        yiaddr: Vec::new(),
+        siaddr: Vec::new(),
+        giaddr: Vec::new(),
+        chaddr: Vec::new(),
+        sname: Vec::new(),
+        file: Vec::new(),
+        options: Vec::new()
+    };
+
+    read_header_into(r, &mut frame)?;
+    Ok(frame)
+}
+
+/*
+ * Read the fixed BOOTP header into an existing frame, reusing the memory of
+ * its fields. The options are left untouched.
+ */
+fn read_header_into<R: Read>(r: &mut R, frame: &mut Frame) -> Result<()> {
+    let mut first = [0; 4];
+
+    read_field(r, &mut first, "op", 0)?;
+
+    // Parse first line, opcode, htype, hlen and hops
+    let op = first[0];
+    let hlen = first[2];
+
+    if let Opcode::Unknown(op) = Opcode::from(op) {
+        return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Invalid opcode {}", op)));
+    }
+
+    if hlen > 16 {
+        return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Hardware address length too long ({} bytes, maximum is 16)", hlen)));
+    }
+
+    frame.op = op;
+    frame.htype = first[1];
+    frame.hlen = hlen;
+    frame.hops = first[3];
+
+    // Parse xid, secs, flags
+    let mut fixed = [0; 8];
+    read_field(r, &mut fixed[..4], "xid", 4)?;
+    read_field(r, &mut fixed[4..6], "secs", 8)?;
+    read_field(r, &mut fixed[6..], "flags", 10)?;
+
+    frame.xid = BigEndian::read_u32(&fixed[..4]);
+    frame.secs = BigEndian::read_u16(&fixed[4..6]);
+    frame.flags = BigEndian::read_u16(&fixed[6..]);
+
+    // Parse adresses
+    read_vec_field(r, &mut frame.ciaddr, 4, "ciaddr", 12)?;
+    read_vec_field(r, &mut frame.yiaddr, 4, "yiaddr", 16)?;
+    read_vec_field(r, &mut frame.siaddr, 4, "siaddr", 20)?;
+    read_vec_field(r, &mut frame.giaddr, 4, "giaddr", 24)?;
+    read_vec_field(r, &mut frame.chaddr, 16, "chaddr", 28)?;
+
+    // Parse strings
+    read_vec_field(r, &mut frame.sname, 64, "sname", 44)?;
+    read_vec_field(r, &mut frame.file, 128, "file", 108)?;
+
+    Ok(())
+}
+
+/*
+ * Read and check the magic cookie. In BOOTP mode a missing or different
+ * cookie is not an error and false is returned.
+ */
+fn read_cookie<R: Read>(r: &mut R, bootp: bool) -> Result<bool> {
+    let mut cookie = [0; 4];
+
+    let checked = read_field(r, &mut cookie, "magic cookie", 236).and_then(|_| match cookie {
+        DHCP_MAGIC_COOKIE => Ok(()),
+        _ => Err(Error::with_kind(ErrorKind::InvalidMagicCookie, "Invalid magic cookie at offset 236"))
+    });
+
+    match checked {
+        Ok(()) => Ok(true),
+        Err(_) if bootp => Ok(false),
+        Err(e) => Err(e)
+    }
+}
+
+/*
+ * Option overload: the file and/or sname fields carry more options. They
+ * are merged into the options list, so option 52 is dropped and the fields
+ * cleared: the frame is written back with every option in the main area.
+ */
+fn apply_overload(frame: &mut Frame) -> Result<()> {
+    let overload = frame.options.iter()
+        .find(|opt| opt.tag == OPTION_OVERLOAD && opt.data.len() == 1)
+        .map(|opt| opt.data[0]);
+
+    let (file, sname) = match overload {
+        Some(1) => (true, false),
+        Some(2) => (false, true),
+        Some(3) => (true, true),
+        _ => return Ok(())
+    };
+
+    if file {
+        parse_options_into(&frame.file, 108, &mut frame.options)?;
+        frame.file.iter_mut().for_each(|b| *b = 0);
+    }
+
+    if sname {
+        parse_options_into(&frame.sname, 44, &mut frame.options)?;
+        frame.sname.iter_mut().for_each(|b| *b = 0);
+    }
+
+    frame.options.retain(|opt| opt.tag != OPTION_OVERLOAD);
+
+    Ok(())
+}
+
+/*
+ * Read a fixed size header field, naming it and its offset on failure
+ */
+fn read_field<R: Read>(r: &mut R, field: &mut [u8], name: &str, offset: usize) -> Result<()> {
+    r.read_exact(field).map_err(|_| {
+        Error::with_kind(ErrorKind::UnexpectedEof, format!("Unexpected end of frame while reading {} at offset {}", name, offset))
+    })
+}
+
+/*
+ * Read a fixed size header field into a Vec, resized to the field size
+ */
+fn read_vec_field<R: Read>(r: &mut R, field: &mut Vec<u8>, len: usize, name: &str, offset: usize) -> Result<()> {
+    field.resize(len, 0);
+    read_field(r, field, name, offset)
+}
+
+/*
+ * Read options from a stream up to the End option or the end of the stream,
+ * appending to opts
+ */
+fn read_options_from<R: Read>(r: &mut R, opts: &mut Vec<Option>) -> Result<()> {
+    let mut offset = 240;
+
+    loop {
+        let mut tag = [0; 1];
+
+        match r.read_exact(&mut tag) {
+            Ok(()) => {},
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into())
+        }
+
+        match tag[0] {
+            OPTION_PAD => {
+                offset += 1;
+                continue
+            },
+            OPTION_END => return Ok(()),
+            _ => {}
+        }
+
+        let mut len = [0; 1];
+        read_field(r, &mut len, "option length", offset + 1)?;
+
+        let mut data = vec![0; len[0] as usize];
+        read_field(r, &mut data, "option data", offset + 2)?;
+
+        offset += 2 + data.len();
+        opts.push(Option { tag: tag[0], len: len[0], data });
+    }
+}
+
+/*
+ * Parse a standalone option stream, such as the options section of a frame,
+ * up to the End option or the end of the buffer. Pad options are skipped and
+ * End is not included.
+ */
+pub fn parse_options(buf: &[u8]) -> Result<Vec<Option>> {
+    let mut opts = Vec::new();
+    parse_options_into(buf, 0, &mut opts)?;
+
+    Ok(opts)
+}
+
+/*
+ * Parse an option stream up to the End option, appending to opts. base is
+ * the offset of the stream in the frame, for error messages.
+ */
+fn parse_options_into(buf: &[u8], base: usize, opts: &mut Vec<Option>) -> Result<()> {
+    let mut pos = 0;
+
+    while pos < buf.len() {
+        let rest = &buf[pos..];
+
+        if rest[0] == OPTION_END {
+            break;
+        }
+
+        let (opt, consumed) = match Option::parse(rest) {
+            Ok(parsed) => parsed,
+            Err(e) => return Err(Error::with_kind(e.kind(), format!("Failed to parse option at offset {}: {}", base + pos, e)))
+        };
+
+        pos += consumed;
+
+        if opt.tag != OPTION_PAD {
+            opts.push(opt);
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Decode an IPv4 address, 0.0.0.0 if the buffer is too short
+ */
+fn bytes_to_ipv4(buf: &[u8]) -> Ipv4Addr {
+    if buf.len() < 4 {
+        return Ipv4Addr::new(0, 0, 0, 0);
+    }
+
+    Ipv4Addr::new(buf[0], buf[1], buf[2], buf[3])
+}
+
+/*
+ * Decode a list of IPv4 addresses, empty if the length is not a multiple of 4
+ */
+fn bytes_to_ipv4_list(buf: &[u8]) -> Vec<Ipv4Addr> {
+    if !buf.len().is_multiple_of(4) {
+        return Vec::new();
+    }
+
+    buf.chunks(4).map(bytes_to_ipv4).collect()
+}
+
+/*
+ * Decode a NUL padded byte buffer as an UTF-8 string
+ */
+fn bytes_to_string(buf: &[u8]) -> Result<String> {
+    match String::from_utf8(trim_nul(buf).to_vec()) {
+        Ok(s) => Ok(s),
+        Err(e) => Err(Error::with_kind(ErrorKind::InvalidUtf8, e.to_string()))
+    }
+}
+
+/*
+ * Decode a DNS encoded name (RFC 1035 section 4.1.4) starting at pos,
+ * following compression pointers, returns it with the position after it.
+ * Pointers must go backwards from the previous one so loops cannot happen.
+ */
+fn decode_dns_name(data: &[u8], pos: usize) -> option::Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut cur = pos;
+    let mut next = None;  // Position after the name, set at the first pointer
+    let mut limit = pos;  // Pointers must point before this
+
+    loop {
+        let len = *data.get(cur)? as usize;
+
+        match len & 0xc0 {
+            0x00 if len == 0 => return Some((labels.join("."), next.unwrap_or(cur + 1))),
+            0x00 => {
+                let label = data.get(cur + 1..cur + 1 + len)?;
+
+                labels.push(String::from_utf8(label.to_vec()).ok()?);
+                cur += 1 + len;
+            },
+            0xc0 => {
+                let target = ((len & 0x3f) << 8) | *data.get(cur + 1)? as usize;
+
+                if target >= limit {
+                    return None;
+                }
+
+                next = next.or(Some(cur + 2));
+                limit = target;
+                cur = target;
+            },
+            _ => return None
+        }
+    }
+}
+
+impl Frame {
+    /*
+     * Construct a Frame structure based on received data
+     */
+    pub fn parse(buf: &[u8]) -> Result<Frame> {
+        Frame::parse_with(buf, false)
+    }
+
+    /*
+     * Parse a BOOTP or DHCP frame: without a magic cookie after the header,
+     * the vendor area is ignored and the frame has no options
+     */
+    pub fn parse_bootp(buf: &[u8]) -> Result<Frame> {
+        Frame::parse_with(buf, true)
+    }
+
+    /*
+     * Read a frame from a stream, up to its End option or the end of the
+     * stream, without buffering the whole frame first
+     */
+    pub fn read_from<R: Read>(r: &mut R) -> Result<Frame> {
+        let mut frame = read_header(r)?;
+        read_cookie(r, false)?;
+
+        read_options_from(r, &mut frame.options)?;
+        apply_overload(&mut frame)?;
+
+        Ok(frame)
+    }
+
+    /*
+     * Parse a frame into an existing one, reusing the memory of its fields
+     * and options list, to save allocations when parsing many frames. The
+     * frame is left in an unspecified state on error.
+     */
+    pub fn parse_into(buf: &[u8], frame: &mut Frame) -> Result<()> {
+        let mut cur = Cursor::new(buf);
+        read_header_into(&mut cur, frame)?;
+
+        frame.options.clear();
+        read_cookie(&mut cur, false)?;
+
+        let start = cur.position() as usize;
+        parse_options_into(&buf[start..], start, &mut frame.options)?;
+
+        apply_overload(frame)
+    }
+
+    /*
+     * Parse a frame, tolerating a missing magic cookie in BOOTP mode
+     */
+    fn parse_with(buf: &[u8], bootp: bool) -> Result<Frame> {
+        let mut cur = Cursor::new(buf);
+        let mut frame = read_header(&mut cur)?;
+
+        if read_cookie(&mut cur, bootp)? {
+            let start = cur.position() as usize;
+            parse_options_into(&buf[start..], start, &mut frame.options)?;
+        }
+
+        apply_overload(&mut frame)?;
+
+        Ok(frame)
+    }
+
+    /*
+     * Return the client's hardware address as a classical MAC address string
+     */
+    pub fn client_mac_string(&self) -> String {
+        let octets: Vec<String> = self.hardware_address().iter().map(|b| format!("{:02x}", b)).collect();
+        octets.join(":")
+    }
+
+    /*
+     * Return the meaningful part of the client hardware address, hlen bytes
+     */
+    pub fn hardware_address(&self) -> &[u8] {
+        let hlen = (self.hlen as usize).min(self.chaddr.len());
+        &self.chaddr[..hlen]
+    }
+
+    /*
+     * Whether the client asked for broadcast replies (top bit of flags)
+     */
+    pub fn broadcast(&self) -> bool {
+        self.bootp_flags().broadcast()
+    }
+
+    /*
+     * Get the opcode (op field)
+     */
+    pub fn opcode(&self) -> Opcode {
+        Opcode::from(self.op)
+    }
+
+    /*
+     * Get the hardware address type (htype field)
+     */
+    pub fn hardware_type(&self) -> HardwareType {
+        HardwareType::from(self.htype)
+    }
+
+    /*
+     * Get the transaction ID (xid field), matching replies to requests
+     */
+    pub fn xid(&self) -> u32 {
+        self.xid
+    }
+
+    /*
+     * Get the flags field as Flags
+     */
+    pub fn bootp_flags(&self) -> Flags {
+        Flags(self.flags)
+    }
+
+    /*
+     * Client IP address (ciaddr field)
+     */
+    pub fn client_ip(&self) -> Ipv4Addr {
+        bytes_to_ipv4(&self.ciaddr)
+    }
+
+    /*
+     * Your (client) IP address, as assigned by the server (yiaddr field)
+     */
+    pub fn your_ip(&self) -> Ipv4Addr {
+        bytes_to_ipv4(&self.yiaddr)
+    }
+
+    /*
+     * Next server IP address (siaddr field)
+     */
+    pub fn server_ip(&self) -> Ipv4Addr {
+        bytes_to_ipv4(&self.siaddr)
+    }
+
+    /*
+     * Relay agent IP address (giaddr field)
+     */
+    pub fn relay_ip(&self) -> Ipv4Addr {
+        bytes_to_ipv4(&self.giaddr)
+    }
+
+    /*
+     * Return the server host name (sname field) as a string
+     */
+    pub fn server_name(&self) -> Result<String> {
+        bytes_to_string(&self.sname)
+    }
+
+    /*
+     * Return the boot file name (file field) as a string
+     */
+    pub fn boot_file(&self) -> Result<String> {
+        bytes_to_string(&self.file)
+    }
+
+    /*
+     * Get an option
+     */
+    pub fn option(&self, tag: u8) -> option::Option<&Option> {
+        self.options.iter().find(|opt| opt.tag == tag)
+    }
+
+    /*
+     * Iterate over the options, in frame order
+     */
+    pub fn iter_options(&self) -> impl Iterator<Item = &Option> {
+        self.options.iter()
+    }
+
+    /*
+     * Iterate over the options as (tag, data) pairs, in frame order
+     */
+    pub fn option_pairs(&self) -> impl Iterator<Item = (u8, &[u8])> {
+        self.options.iter().map(|opt| (opt.tag, opt.data.as_slice()))
+    }
+
+    /*
+     * Get a mutable reference to an option
+     */
+    pub fn option_mut(&mut self, tag: u8) -> option::Option<&mut Option> {
+        self.options.iter_mut().find(|opt| opt.tag == tag)
+    }
+
+    /*
+     * Get the data of all instances of an option joined in order (RFC 3396)
+     */
+    pub fn concatenated_option(&self, tag: u8) -> option::Option<Vec<u8>> {
+        if !self.has_option(tag) {
+            return None;
+        }
+
+        let mut data = Vec::new();
+        for opt in self.options.iter().filter(|opt| opt.tag == tag) {
+            data.extend(opt.data.iter());
+        }
+
+        Some(data)
+    }
+
+    /*
+     * Check whether an option is present
+     */
+    pub fn has_option(&self, tag: u8) -> bool {
+        self.option(tag).is_some()
+    }
+
+    /*
+     * Get the option tags requested by the client (option 55)
+     */
+    pub fn requested_parameters(&self) -> Vec<u8> {
+        match self.option(OPTION_PARAMETER_LIST) {
+            Some(opt) => opt.data.clone(),
+            None => Vec::new()
+        }
+    }
+
+    /*
+     * Get the subnet mask (option 1)
+     */
+    pub fn subnet_mask(&self) -> option::Option<Ipv4Addr> {
+        self.option_ipv4(OPTION_SUBNET_MASK)
+    }
+
+    /*
+     * Get the routers (option 3), empty if absent or malformed
+     */
+    pub fn routers(&self) -> Vec<Ipv4Addr> {
+        match self.option(OPTION_ROUTER) {
+            Some(opt) => bytes_to_ipv4_list(&opt.data),
+            None => Vec::new()
+        }
+    }
+
+    /*
+     * Get the DNS servers (option 6), empty if absent or malformed
+     */
+    pub fn dns_servers(&self) -> Vec<Ipv4Addr> {
+        match self.option(OPTION_DOMAIN_SERVER) {
+            Some(opt) => bytes_to_ipv4_list(&opt.data),
+            None => Vec::new()
+        }
+    }
+
+    /*
+     * Get the domain name (option 15), None if absent or not valid UTF-8
+     */
+    pub fn domain_name(&self) -> option::Option<String> {
+        self.option(OPTION_DOMAIN_NAME).and_then(|opt| opt.value_as_string().ok())
+    }
+
+    /*
+     * Get the client host name (option 12)
+     */
+    pub fn host_name(&self) -> option::Option<String> {
+        self.option(OPTION_HOST_NAME).and_then(|opt| opt.value_as_string().ok())
+    }
+
+    /*
+     * Get the client FQDN (option 81) as its flags byte and domain name,
+     * decoding the DNS wire format when the E flag is set (RFC 4702)
+     */
+    pub fn client_fqdn(&self) -> option::Option<(u8, String)> {
+        let data = &self.option(OPTION_CLIENT_FQDN)?.data;
+
+        // Flags, then the two deprecated RCODE bytes
+        if data.len() < 3 {
+            return None;
+        }
+
+        let flags = data[0];
+        let name = &data[3..];
+
+        if flags & 0x04 == 0 {
+            return bytes_to_string(name).ok().map(|name| (flags, name));
+        }
+
+        let mut labels = Vec::new();
+        let mut pos = 0;
+
+        while pos < name.len() && name[pos] != 0 {
+            let end = pos + 1 + name[pos] as usize;
+
+            if end > name.len() {
+                return None;
+            }
+
+            labels.push(bytes_to_string(&name[pos + 1..end]).ok()?);
+            pos = end;
+        }
+
+        Some((flags, labels.join(".")))
+    }
+
+    /*
+     * Get the domain search list (option 119), None if absent or malformed
+     */
+    pub fn domain_search(&self) -> option::Option<Vec<String>> {
+        let data = self.concatenated_option(OPTION_DOMAIN_SEARCH)?;
+        let mut names = Vec::new();
+        let mut pos = 0;
+
+        while pos < data.len() {
+            let (name, next) = decode_dns_name(&data, pos)?;
+
+            names.push(name);
+            pos = next;
+        }
+
+        Some(names)
+    }
+
+    /*
+     * Get the classless static routes (option 121, RFC 3442), None if absent
+     * or malformed. A prefix length of 0 is the default route.
+     */
+    pub fn classless_routes(&self) -> option::Option<Vec<ClasslessRoute>> {
+        let data = self.concatenated_option(OPTION_CLASSLESS_ROUTE)?;
+        let mut routes = Vec::new();
+        let mut pos = 0;
+
+        while pos < data.len() {
+            let prefix_len = data[pos];
+            if prefix_len > 32 {
+                return None;
+            }
+
+            // Only the significant octets of the destination are sent
+            let octets = (prefix_len as usize).div_ceil(8);
+            let mut destination = [0; 4];
+            destination[..octets].copy_from_slice(data.get(pos + 1..pos + 1 + octets)?);
+
+            let router = data.get(pos + 1 + octets..pos + 5 + octets)?;
+
+            routes.push((Ipv4Addr::from(destination), prefix_len, bytes_to_ipv4(router)));
+            pos += 5 + octets;
+        }
+
+        Some(routes)
+    }
+
+    /*
+     * Get the user classes (option 77), each prefixed by its length (RFC
+     * 3004). Many clients send a single unprefixed string instead, which is
+     * returned as is when the lengths do not add up.
+     */
+    pub fn user_class(&self) -> option::Option<Vec<String>> {
+        let data = &self.option(OPTION_USER_CLASS)?.data;
+
+        match split_length_prefixed(data) {
+            Some(ref classes) if !classes.is_empty() => classes.iter().map(|class| bytes_to_string(class).ok()).collect(),
+            _ => bytes_to_string(data).ok().map(|class| vec![class])
+        }
+    }
+
+    /*
+     * Get the vendor classes (option 124) by IANA enterprise number, None
+     * if absent or malformed (RFC 3925 section 3)
+     */
+    pub fn vi_vendor_class(&self) -> option::Option<Vec<(u32, Vec<Vec<u8>>)>> {
+        let data = &self.option(OPTION_VI_VENDOR_CLASS)?.data;
+
+        split_enterprise_blocks(data)?.into_iter()
+            .map(|(enterprise, block)| split_length_prefixed(block).map(|classes| (enterprise, classes)))
+            .collect()
+    }
+
+    /*
+     * Get the vendor sub-options (option 125) by IANA enterprise number as
+     * code/value pairs, None if absent or malformed (RFC 3925 section 4)
+     */
+    pub fn vi_vendor_specific(&self) -> option::Option<Vec<(u32, SubOptions)>> {
+        let data = &self.option(OPTION_VI_VENDOR_SPECIFIC)?.data;
+
+        split_enterprise_blocks(data)?.into_iter()
+            .map(|(enterprise, block)| split_sub_options(block).map(|subopts| (enterprise, subopts)))
+            .collect()
+    }
+
+    /*
+     * Get the vendor specific information (option 43)
+     */
+    pub fn vendor_specific(&self) -> option::Option<&[u8]> {
+        self.option(OPTION_VENDOR_SPECIFIC).map(|opt| opt.data.as_slice())
+    }
+
+    /*
+     * Get the vendor class identifier (option 60), e.g. "PXEClient:..."
+     */
+    pub fn vendor_class_identifier(&self) -> option::Option<String> {
+        self.option(OPTION_CLASS_ID).and_then(|opt| opt.value_as_string().ok())
+    }
+
+    /*
+     * Get the TFTP server name (option 66)
+     */
+    pub fn tftp_server_name(&self) -> option::Option<String> {
+        self.option(OPTION_SERVER_NAME).and_then(|opt| opt.value_as_string().ok())
+    }
+
+    /*
+     * Get the boot file name (option 67), see boot_file for the file field
+     */
+    pub fn bootfile_name(&self) -> option::Option<String> {
+        self.option(OPTION_BOOTFILE_NAME).and_then(|opt| opt.value_as_string().ok())
+    }
+
+    /*
+     * Get the client system architecture (option 93, RFC 4578), e.g.
+     * ARCH_EFI_X86_64. Clients may list several, the first is returned.
+     */
+    pub fn client_arch(&self) -> option::Option<u16> {
+        let data = &self.option(OPTION_CLIENT_ARCH)?.data;
+
+        if data.len() < 2 || !data.len().is_multiple_of(2) {
+            return None;
+        }
+
+        Some(BigEndian::read_u16(data))
+    }
+
+    /*
+     * Get the client network interface identifier (option 94, RFC 4578) as
+     * its type, 1 for UNDI, and major and minor revisions
+     */
+    pub fn network_interface(&self) -> option::Option<(u8, u8, u8)> {
+        match self.option(OPTION_CLIENT_NDI)?.data.as_slice() {
+            &[kind, major, minor] => Some((kind, major, minor)),
+            _ => None
+        }
+    }
+
+    /*
+     * Get the client machine UUID (option 97, RFC 4578), None unless it is
+     * a type 0 identifier of 16 bytes
+     */
+    pub fn client_uuid(&self) -> option::Option<[u8; 16]> {
+        let data = &self.option(OPTION_CLIENT_UUID)?.data;
+
+        if data.len() != 17 || data[0] != 0 {
+            return None;
+        }
+
+        let mut uuid = [0; 16];
+        uuid.copy_from_slice(&data[1..]);
+
+        Some(uuid)
+    }
+
+    /*
+     * Get the address lease time in seconds (option 51)
+     */
+    pub fn lease_time(&self) -> option::Option<u32> {
+        self.option_u32(OPTION_ADDRESS_LEASE_TIME)
+    }
+
+    /*
+     * Get the renewal (T1) time in seconds (option 58)
+     */
+    pub fn renewal_time(&self) -> option::Option<u32> {
+        self.option_u32(OPTION_RENEWAL_TIME)
+    }
+
+    /*
+     * Get the rebinding (T2) time in seconds (option 59)
+     */
+    pub fn rebinding_time(&self) -> option::Option<u32> {
+        self.option_u32(OPTION_REBINDING_TIME)
+    }
+
+    /*
+     * Get the maximum DHCP message size the client accepts (option 57)
+     */
+    pub fn max_message_size(&self) -> option::Option<u16> {
+        self.option(OPTION_DHCP_MAX_MSG_SIZE).and_then(|opt| opt.as_u16().ok())
+    }
+
+    /*
+     * Get the IP address requested by the client (option 50)
+     */
+    pub fn requested_ip(&self) -> option::Option<Ipv4Addr> {
+        self.option_ipv4(OPTION_ADDRESS_REQUEST)
+    }
+
+    /*
+     * Get the subnet the client wants an address from (option 118, RFC
+     * 3011), taking precedence over giaddr
+     */
+    pub fn subnet_selection(&self) -> option::Option<Ipv4Addr> {
+        self.option_ipv4(OPTION_SUBNET_SELECTION)
+    }
+
+    /*
+     * Get the identifier of the server the message is for (option 54)
+     */
+    pub fn server_identifier(&self) -> option::Option<Ipv4Addr> {
+        self.option_ipv4(OPTION_DHCP_SERVER_ID)
+    }
+
+    /*
+     * Get the raw client identifier (option 61)
+     */
+    pub fn client_identifier(&self) -> option::Option<Vec<u8>> {
+        self.option(OPTION_CLIENT_ID).map(|opt| opt.data.clone())
+    }
+
+    /*
+     * Get the client identifier (option 61) split into its type byte and
+     * identifier, None if absent or empty
+     */
+    pub fn client_identifier_parts(&self) -> option::Option<(u8, Vec<u8>)> {
+        match self.option(OPTION_CLIENT_ID) {
+            Some(opt) if !opt.data.is_empty() => Some((opt.data[0], opt.data[1..].to_vec())),
+            _ => None
+        }
+    }
+
+    /*
+     * Get the Relay Agent Information (option 82) sub-options as code/value
+     * pairs, None if absent or malformed
+     */
+    pub fn relay_agent_info(&self) -> option::Option<SubOptions> {
+        split_sub_options(&self.option(OPTION_RELAY_AGENT_INFO)?.data)
+    }
+
+    /*
+     * Get the key identifying the client for leases: the client identifier
+     * when present, the hardware address otherwise (RFC 2131 section 4.2)
+     */
+    pub fn client_key(&self) -> Vec<u8> {
+        match self.client_identifier() {
+            Some(ref id) if !id.is_empty() => id.clone(),
+            _ => self.hardware_address().to_vec()
+        }
+    }
+
+    /*
+     * Decode a single IPv4 address option value
+     */
+    fn option_ipv4(&self, tag: u8) -> option::Option<Ipv4Addr> {
+        self.option(tag).and_then(|opt| opt.as_ipv4().ok())
+    }
+
+    /*
+     * Decode a 32 bit big endian option value
+     */
+    fn option_u32(&self, tag: u8) -> option::Option<u32> {
+        self.option(tag).and_then(|opt| opt.as_u32().ok())
+    }
+
+    /*
+     * Get the DHCP message type (option 53), if present and well formed
+     */
+    pub fn message_type(&self) -> option::Option<MessageType> {
+        match self.option(OPTION_DHCP_MSG_TYPE) {
+            Some(opt) if opt.data.len() == 1 => Some(MessageType::from(opt.data[0])),
+            _ => None
+        }
+    }
+
+    /*
+     * Get the client state a DHCPREQUEST was sent from, from its server
+     * identifier, requested IP and ciaddr. None for other messages and
+     * invalid combinations.
+     *
+     * A renewing client unicasts to its server while a rebinding one
+     * broadcasts, which the frame alone does not show: only a relayed
+     * request, necessarily broadcast, is reported as Rebinding.
+     */
+    pub fn dhcp_state(&self) -> option::Option<RequestState> {
+        if self.message_type() != Some(MessageType::Request) {
+            return None;
+        }
+
+        let server_id = self.has_option(OPTION_DHCP_SERVER_ID);
+        let requested = self.has_option(OPTION_ADDRESS_REQUEST);
+        let ciaddr = !self.client_ip().is_unspecified();
+
+        match (server_id, requested, ciaddr) {
+            (true, true, false) => Some(RequestState::Selecting),
+            (false, true, false) => Some(RequestState::InitReboot),
+            (false, false, true) if self.relay_ip().is_unspecified() => Some(RequestState::Renewing),
+            (false, false, true) => Some(RequestState::Rebinding),
+            _ => None
+        }
+    }
+
+    /*
+     * Compare the content of two frames, ignoring the layout: NUL padding of
+     * the fixed fields, chaddr bytes past hlen, Pad and End options and the
+     * order of options with different tags
+     */
+    pub fn logical_eq(&self, other: &Frame) -> bool {
+        (self.op, self.htype, self.hlen, self.hops, self.xid, self.secs, self.flags) ==
+            (other.op, other.htype, other.hlen, other.hops, other.xid, other.secs, other.flags) &&
+            self.client_ip() == other.client_ip() &&
+            self.your_ip() == other.your_ip() &&
+            self.server_ip() == other.server_ip() &&
+            self.relay_ip() == other.relay_ip() &&
+            self.hardware_address() == other.hardware_address() &&
+            trim_nul(&self.sname) == trim_nul(&other.sname) &&
+            trim_nul(&self.file) == trim_nul(&other.file) &&
+            self.sorted_options() == other.sorted_options()
+    }
+
+    /*
+     * The options as tag/data pairs sorted by tag, without Pad and End. The
+     * sort is stable so split options keep their order.
+     */
+    fn sorted_options(&self) -> Vec<(u8, &[u8])> {
+        let mut opts: Vec<(u8, &[u8])> = self.option_pairs()
+            .filter(|&(tag, _)| tag != OPTION_PAD && tag != OPTION_END)
+            .collect();
+
+        opts.sort_by_key(|&(tag, _)| tag);
+        opts
+    }
+}
+
+/*
+ * Split data made of values each prefixed by a length byte
+ */
+fn split_length_prefixed(data: &[u8]) -> option::Option<Vec<Vec<u8>>> {
+    let mut values = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let end = pos + 1 + data[pos] as usize;
+
+        values.push(data.get(pos + 1..end)?.to_vec());
+        pos = end;
+    }
+
+    Some(values)
+}
+
+/*
+ * Split data made of code, length and value sub-options
+ */
+fn split_sub_options(data: &[u8]) -> option::Option<SubOptions> {
+    let mut subopts = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let len = *data.get(pos + 1)? as usize;
+
+        subopts.push((data[pos], data.get(pos + 2..pos + 2 + len)?.to_vec()));
+        pos += 2 + len;
+    }
+
+    Some(subopts)
+}
+
+/*
+ * Split vendor-identifying option data into its blocks: a 32 bit enterprise
+ * number, then a length byte and that many bytes of data
+ */
+fn split_enterprise_blocks(data: &[u8]) -> option::Option<Vec<(u32, &[u8])>> {
+    let mut blocks = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let enterprise = BigEndian::read_u32(data.get(pos..pos + 4)?);
+        let len = *data.get(pos + 4)? as usize;
+
+        blocks.push((enterprise, data.get(pos + 5..pos + 5 + len)?));
+        pos += 5 + len;
+    }
+
+    Some(blocks)
+}
+
+/*
+ * Strip the trailing NUL bytes of a fixed size field
+ */
+fn trim_nul(buf: &[u8]) -> &[u8] {
+    let end = buf.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    &buf[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    /*
+     * Build a DISCOVER header followed by the magic cookie and raw option bytes
+     */
+    fn frame_with_options(options: &[u8]) -> Vec<u8> {
+        let mut data = vec![
+            0x01, 0x01, 0x06, 0x00, 0x6e, 0x86, 0x44, 0x4c,
+            0x00, 0x08, 0x00, 0x00
+        ];
+
+        data.extend(vec![0; 16 + 16 + 64 + 128]);
+        data.extend(vec![0x63, 0x82, 0x53, 0x63]);
+        data.extend(options.iter());
+
+        data
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_option_empty_invalid() {
+        let data = [];
+        super::Option::parse(&data).unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_option_too_short_invalid() {
+        let data = [0x01];
+        super::Option::parse(&data).unwrap();
+    }
+
+    #[test]
+    fn test_iter_options() {
+        let data = frame_with_options(&[
+            0x35, 0x01, 0x01,
+            0x32, 0x04, 0xc0, 0xa8, 0x01, 0x0a,
+            0x0c, 0x03, 0x70, 0x78, 0x65,
+            0x37, 0x02, 0x01, 0x03,
+            0xff
+        ]);
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.iter_options().count(), 4);
+        assert_eq!(frame.iter_options().map(|opt| opt.tag).collect::<Vec<u8>>(), [53, 50, 12, 55]);
+
+        let pairs: Vec<(u8, &[u8])> = frame.option_pairs().collect();
+
+        assert_eq!(pairs.len(), 4);
+        assert_eq!(pairs[2], (12, &b"pxe"[..]));
+    }
+
+    #[test]
+    fn test_relay_agent_info() {
+        let data = frame_with_options(&[
+            0x52, 0x0d,
+            0x01, 0x04, 0x00, 0x01, 0x00, 0x2a, // Circuit ID
+            0x02, 0x05, 0x72, 0x61, 0x63, 0x6b, 0x31, // Remote ID
+            0xff
+        ]);
+
+        let frame = super::Frame::parse(&data).unwrap();
+        let info = frame.relay_agent_info().unwrap();
+
+        assert_eq!(info, vec![(1, vec![0x00, 0x01, 0x00, 0x2a]), (2, b"rack1".to_vec())]);
+
+        // A sub-option running past the end of the option
+        let data = frame_with_options(&[0x52, 0x03, 0x01, 0x04, 0x00, 0xff]);
+        assert!(super::Frame::parse(&data).unwrap().relay_agent_info().is_none());
+    }
+
+    #[test]
+    fn test_host_name_fqdn() {
+        let data = frame_with_options(&[
+            0x0c, 0x07, 0x6c, 0x61, 0x70, 0x74, 0x6f, 0x70, 0x00,
+            0x51, 0x0f, 0x05, 0x00, 0x00, 0x06, 0x6c, 0x61, 0x70, 0x74, 0x6f, 0x70, 0x03, 0x6c, 0x61, 0x6e, 0x00,
+            0xff
+        ]);
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.host_name().unwrap().as_str(), "laptop");
+        assert_eq!(frame.client_fqdn().unwrap(), (0x05, "laptop.lan".to_string()));
+
+        // ASCII encoded name, E flag clear
+        let data = frame_with_options(&[0x51, 0x0d, 0x01, 0x00, 0x00, 0x6c, 0x61, 0x70, 0x74, 0x6f, 0x70, 0x2e, 0x6c, 0x61, 0x6e, 0xff]);
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.client_fqdn().unwrap(), (0x01, "laptop.lan".to_string()));
+        assert!(frame.host_name().is_none());
+
+        // Label running past the end of the option
+        let data = frame_with_options(&[0x51, 0x05, 0x04, 0x00, 0x00, 0x08, 0x6c, 0xff]);
+        assert!(super::Frame::parse(&data).unwrap().client_fqdn().is_none());
+    }
+
+    #[test]
+    fn test_frame_read_from() {
+        let data = frame_with_options(&[0x35, 0x01, 0x01, 0x00, 0x33, 0x04, 0x00, 0x00, 0x0e, 0x10, 0xff, 0x42, 0x42]);
+        let mut cur = ::std::io::Cursor::new(&data[..]);
+
+        let frame = super::Frame::read_from(&mut cur).unwrap();
+
+        assert_eq!(frame, super::Frame::parse(&data).unwrap());
+        assert_eq!(frame.lease_time(), Some(3600));
+
+        // Stopped after End, the trailing bytes are left in the stream
+        assert_eq!(cur.position() as usize, data.len() - 2);
+
+        // Short reads
+        let e = super::Frame::read_from(&mut ::std::io::Cursor::new(&data[..100])).unwrap_err();
+        assert_eq!(e.kind(), ::common::ErrorKind::UnexpectedEof);
+
+        let e = super::Frame::read_from(&mut ::std::io::Cursor::new(&data[..247])).unwrap_err();
+        assert_eq!(e.kind(), ::common::ErrorKind::UnexpectedEof);
+        assert_eq!(e.to_string().as_str(), "Unexpected end of frame while reading option data at offset 246");
+    }
+
+    #[test]
+    fn test_frame_invalid_opcode() {
+        let mut data = frame_with_options(&[0xff]);
+        assert_eq!(super::Frame::parse(&data).unwrap().opcode(), super::Opcode::Request);
+
+        data[0] = 3;
+        let e = super::Frame::parse(&data).unwrap_err();
+
+        assert_eq!(e.kind(), ::common::ErrorKind::InvalidValue);
+        assert_eq!(e.to_string().as_str(), "Invalid opcode 3");
+        assert_eq!(super::Opcode::from(3), super::Opcode::Unknown(3));
+        assert_eq!(u8::from(super::Opcode::Reply), 2);
+    }
+
+    #[test]
+    fn test_frame_truncated_offset() {
+        let data = frame_with_options(&[0xff]);
+        let e = super::Frame::parse(&data[..36]).unwrap_err();
+
+        assert_eq!(e.kind(), ::common::ErrorKind::UnexpectedEof);
+        assert_eq!(e.to_string().as_str(), "Unexpected end of frame while reading chaddr at offset 28");
+
+        let data = frame_with_options(&[0x35, 0x01, 0x01, 0x0c, 0x05, 0x61]);
+        let e = super::Frame::parse(&data).unwrap_err();
+
+        assert_eq!(e.to_string().as_str(), "Failed to parse option at offset 243: Option length exceeds buffer");
+    }
+
+    #[test]
+    fn test_option_typed_values() {
+        let byte = super::Option::parse(&[0x35, 0x01, 0x05]).unwrap().0;
+        let short = super::Option::parse(&[0x39, 0x02, 0x05, 0xdc]).unwrap().0;
+        let long = super::Option::parse(&[0x33, 0x04, 0x00, 0x01, 0x51, 0x80]).unwrap().0;
+        let ip = super::Option::parse(&[0x36, 0x04, 0xc0, 0xa8, 0x01, 0x01]).unwrap().0;
+
+        assert_eq!(byte.as_u8().unwrap(), 5);
+        assert_eq!(short.as_u16().unwrap(), 1500);
+        assert_eq!(long.as_u32().unwrap(), 86400);
+        assert_eq!(ip.as_ipv4().unwrap(), ::std::net::Ipv4Addr::new(192, 168, 1, 1));
+
+        // Length mismatches
+        assert_eq!(byte.as_u16().unwrap_err().kind(), ::common::ErrorKind::InvalidOption);
+        assert!(short.as_u8().is_err());
+        assert!(short.as_u32().is_err());
+        assert!(byte.as_ipv4().is_err());
+        assert!(long.as_u16().is_err());
+    }
+
+    #[test]
+    fn test_option_length_exceeds_buffer() {
+        // Claims 50 bytes of data, only 3 present
+        let data = [0x0c, 0x32, 0x61, 0x62, 0x63];
+        let e = super::Option::parse(&data).unwrap_err();
+
+        assert_eq!(e.kind(), ::common::ErrorKind::UnexpectedEof);
+        assert_eq!(e.to_string().as_str(), "Option length exceeds buffer");
+        assert!(super::Frame::parse(&frame_with_options(&data)).is_err());
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let opts = super::parse_options(&[0x35, 0x01, 0x01, 0x00, 0x0c, 0x02, 0x61, 0x62, 0xff, 0x01]).unwrap();
+
+        assert_eq!(opts.len(), 2);
+        assert_eq!(opts[0].data, [0x01]);
+        assert_eq!(opts[1].tag, 0x0c);
+
+        assert_eq!(super::parse_options(&[0x35, 0x01]).unwrap_err().kind(), ::common::ErrorKind::UnexpectedEof);
+        assert!(super::parse_options(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_option_consumed() {
+        let (opt, consumed) = super::Option::parse(&[0x35, 0x01, 0x05, 0xff]).unwrap();
+
+        assert_eq!(opt.data, [0x05]);
+        assert_eq!(consumed, 3);
+        assert_eq!(super::Option::parse(&[0x00, 0x35]).unwrap().1, 1);
+        assert_eq!(super::Option::parse(&[0xff]).unwrap().1, 1);
+    }
+
+    #[test]
+    fn test_domain_search_compressed() {
+        // "eng.example.com", then "example.com" as a pointer to offset 4
+        let data = [
+            0x77, 0x16,
+            0x03, 0x65, 0x6e, 0x67, 0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00,
+            0x03, 0x66, 0x6f, 0x6f, 0xc0
+        ];
+
+        // Truncated pointer
+        assert_eq!(super::Frame::parse(&frame_with_options(&data)).unwrap().domain_search(), None);
+
+        let mut data = data.to_vec();
+        data[1] = 0x17;
+        data.push(0x04);
+
+        let frame = super::Frame::parse(&frame_with_options(&data)).unwrap();
+        assert_eq!(frame.domain_search().unwrap(), ["eng.example.com", "foo.example.com"]);
+
+        // A pointer to itself
+        let looping = [0x77, 0x02, 0xc0, 0x00];
+        assert_eq!(super::Frame::parse(&frame_with_options(&looping)).unwrap().domain_search(), None);
+    }
+
+    #[test]
+    fn test_logical_eq() {
+        let padded = frame_with_options(&[0x00, 0x35, 0x01, 0x01, 0x00, 0x00, 0x33, 0x04, 0x00, 0x00, 0x0e, 0x10, 0xff]);
+        let plain = frame_with_options(&[0x33, 0x04, 0x00, 0x00, 0x0e, 0x10, 0x35, 0x01, 0x01]);
+
+        let a = super::Frame::parse(&padded).unwrap();
+        let mut b = super::Frame::parse(&plain).unwrap();
+
+        b.sname.truncate(10);
+        b.chaddr[10] = 0xaa;
+        b.options.push(super::Option { tag: 0, len: 0, data: Vec::new() });
+
+        assert_ne!(a, b);
+        assert!(a.logical_eq(&b));
 
-        match String::from_utf8(data) {
-            Ok(s) => Ok(s),
-            Err(e) => Err(Error::new(e.description()))
-        }
+        b.file = b"pxelinux.0".to_vec();
+        assert!(!a.logical_eq(&b));
     }
-}
 
-impl Frame {
-    /*
-     * Construct a Frame structure based on received data
-     */
-    pub fn parse(buf: &[u8]) -> Result<Frame> {
-        if buf.len() < 44 {
-            return Err(Error::new("Frame too short"));
-        }
+    #[test]
+    fn test_dhcp_state() {
+        let request = [0x35, 0x01, 0x03];
+        let server_id = [0x36, 0x04, 0x0a, 0x00, 0x00, 0x01];
+        let requested = [0x32, 0x04, 0x0a, 0x00, 0x00, 0x05];
 
-        let mut cur = Cursor::new(buf);
-        let mut first = [0; 4];
-
-        try!(cur.read_exact(&mut first));
-
-        // Parse first line, opcode, htype, hlen and hops
-        let op = first[0];
-        let htype = first[1];
-        let hlen = first[2];
-        let hops = first[3];
-
-        // Parse xid, secs, flags
-        let xid = try!(cur.read_u32::<BigEndian>());
-        let secs = try!(cur.read_u16::<BigEndian>());
-        let flags = try!(cur.read_u16::<BigEndian>());
-
-        // Parse adresses
-        let mut ciaddr = vec![0; 4];
-        let mut yiaddr = vec![0; 4];
-        let mut siaddr = vec![0; 4];
-        let mut giaddr = vec![0; 4];
-        let mut chaddr = vec![0; 16];
-
-        try!(cur.read_exact(&mut ciaddr));
-        try!(cur.read_exact(&mut yiaddr));
-        try!(cur.read_exact(&mut siaddr));
-        try!(cur.read_exact(&mut giaddr));
-        try!(cur.read_exact(&mut chaddr));
-
-        // Parse strings
-        let mut sname = vec![0; 64];
-        let mut file = vec![0; 128];
-
-        try!(cur.read_exact(&mut sname));
-        try!(cur.read_exact(&mut file));
-
-        // Magic cookie
-        let mut cookie = vec![0; 4];
-        try!(cur.read_exact(&mut cookie));
-
-        let mut opts = Vec::new();
-        while cur.position() < buf.len() as u64 {
-            {
-                let buf = cur.get_ref();
-                let buf = &buf[cur.position() as usize..];
-
-                match Option::parse(buf) {
-                    Ok(opt) => {
-                        if opt.tag == 255 {
-                            break
-                        }
-
-                        opts.push(opt)
-                    },
-                    Err(e) => return Err(Error::new(format!("Failed to parse option: {}", e)))
-                };
+        let state = |options: &[&[u8]], ciaddr: bool, giaddr: bool| {
+            let mut data = frame_with_options(&options.concat());
+
+            if ciaddr {
+                data[12..16].copy_from_slice(&[10, 0, 0, 5]);
             }
 
-            let pos = cur.position();
-            let opt = opts.last().unwrap();
-
-            cur.set_position(pos + 2 + opt.data.len() as u64);
-        }
-
-        // Construct object
-        Ok(Frame {
-            op: op,
-            htype: htype,
-            hlen: hlen,
-            hops: hops,
-            xid: xid,
-            secs: secs,
-            flags: flags,
-            ciaddr: ciaddr,
-            yiaddr: yiaddr,
-            siaddr: siaddr,
-            giaddr: giaddr,
-            chaddr: chaddr,
-            sname: sname,
-            file: file,
-            options: opts
-        })
+            if giaddr {
+                data[24..28].copy_from_slice(&[10, 0, 1, 1]);
+            }
+
+            super::Frame::parse(&data).unwrap().dhcp_state()
+        };
+
+        assert_eq!(state(&[&request, &server_id, &requested], false, false), Some(super::RequestState::Selecting));
+        assert_eq!(state(&[&request, &requested], false, false), Some(super::RequestState::InitReboot));
+        assert_eq!(state(&[&request], true, false), Some(super::RequestState::Renewing));
+        assert_eq!(state(&[&request], true, true), Some(super::RequestState::Rebinding));
+
+        // Invalid combinations and other messages
+        assert_eq!(state(&[&request, &server_id, &requested], true, false), None);
+        assert_eq!(state(&[&request], false, false), None);
+        assert_eq!(state(&[&[0x35, 0x01, 0x01], &requested], false, false), None);
     }
 
-    /*
-     * Return the client's hardware address as a classical MAC address string
-     */
-    pub fn client_mac_string(&self) -> String {
-        format!("{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", self.chaddr[0], self.chaddr[1], self.chaddr[2], self.chaddr[3], self.chaddr[4], self.chaddr[5])
+    #[test]
+    fn test_classless_routes() {
+        let data = frame_with_options(&[0x79, 0x0d, 0x18, 0xc0, 0xa8, 0x02, 0xc0, 0xa8, 0x01, 0x01, 0x00, 0x0a, 0x00, 0x00, 0x01, 0xff]);
+        let routes = super::Frame::parse(&data).unwrap().classless_routes().unwrap();
+
+        assert_eq!(routes, [
+            (super::Ipv4Addr::new(192, 168, 2, 0), 24, super::Ipv4Addr::new(192, 168, 1, 1)),
+            (super::Ipv4Addr::new(0, 0, 0, 0), 0, super::Ipv4Addr::new(10, 0, 0, 1))
+        ]);
+
+        // Prefix too long, router cut short
+        let data = frame_with_options(&[0x79, 0x05, 0x21, 0x0a, 0x00, 0x00, 0x01, 0xff]);
+        assert!(super::Frame::parse(&data).unwrap().classless_routes().is_none());
+
+        let data = frame_with_options(&[0x79, 0x04, 0x08, 0x0a, 0xc0, 0xa8, 0xff]);
+        assert!(super::Frame::parse(&data).unwrap().classless_routes().is_none());
     }
 
-    /*
-     * Get an option
-     */
-    pub fn option(&self, tag: u8) -> option::Option<&Option> {
-        for opt in &self.options {
-            if opt.tag == tag {
-                return Some(opt);
-            }
-        }
+    #[test]
+    fn test_user_class() {
+        let prefixed = [0x4d, 0x0a, 0x04, 0x69, 0x50, 0x58, 0x45, 0x04, 0x6c, 0x61, 0x62, 0x31];
+        let frame = super::Frame::parse(&frame_with_options(&prefixed)).unwrap();
+        assert_eq!(frame.user_class().unwrap(), ["iPXE", "lab1"]);
 
-        None
+        // Windows style, a plain string
+        let plain = [0x4d, 0x04, 0x69, 0x50, 0x58, 0x45];
+        let frame = super::Frame::parse(&frame_with_options(&plain)).unwrap();
+        assert_eq!(frame.user_class().unwrap(), ["iPXE"]);
     }
-}
 
-#[cfg(test)]
-mod tests {
     #[test]
-    #[should_panic]
-    fn test_option_empty_invalid() {
-        let data = [];
-        super::Option::parse(&data).unwrap();
+    fn test_vi_vendor_options() {
+        // Enterprise 3561 (Broadband Forum), sub-options 1 and 2
+        let specific = [
+            0x7d, 0x0e,
+            0x00, 0x00, 0x0d, 0xe9, 0x09,
+            0x01, 0x03, 0x41, 0x42, 0x43,
+            0x02, 0x02, 0x00, 0x2a
+        ];
+
+        let frame = super::Frame::parse(&frame_with_options(&specific)).unwrap();
+        assert_eq!(frame.vi_vendor_specific().unwrap(), [(3561, vec![(1, b"ABC".to_vec()), (2, vec![0x00, 0x2a])])]);
+
+        let class = [0x7c, 0x0a, 0x00, 0x00, 0x0d, 0xe9, 0x05, 0x04, 0x74, 0x65, 0x73, 0x74];
+        let frame = super::Frame::parse(&frame_with_options(&class)).unwrap();
+        assert_eq!(frame.vi_vendor_class().unwrap(), [(3561, vec![b"test".to_vec()])]);
+
+        // Block length past the end of the option
+        let broken = [0x7d, 0x06, 0x00, 0x00, 0x0d, 0xe9, 0x09, 0x01];
+        assert!(super::Frame::parse(&frame_with_options(&broken)).unwrap().vi_vendor_specific().is_none());
     }
 
     #[test]
-    #[should_panic]
-    fn test_option_too_short_invalid() {
-        let data = [0x00];
-        super::Option::parse(&data).unwrap();
+    fn test_parse_into_reused() {
+        let mut frame = super::Frame::parse(&frame_with_options(&[0x35, 0x01, 0x01, 0x37, 0x03, 0x01, 0x03, 0x06])).unwrap();
+
+        let mut overloaded = frame_with_options(&[0x34, 0x01, 0x01, 0x35, 0x01, 0x03, 0xff]);
+        overloaded[108..112].copy_from_slice(&[0x33, 0x02, 0xab, 0xcd]);
+
+        let inputs = [
+            frame_with_options(&[0x35, 0x01, 0x03]),
+            overloaded,
+            frame_with_options(&[0x00, 0x0c, 0x03, 0x61, 0x62, 0x63, 0xff])
+        ];
+
+        for input in inputs.iter() {
+            super::Frame::parse_into(input, &mut frame).unwrap();
+            assert_eq!(frame, super::Frame::parse(input).unwrap());
+        }
+
+        assert!(super::Frame::parse_into(&inputs[0][..100], &mut frame).is_err());
     }
 
     #[test]
@@ -191,7 +1438,7 @@ mod tests {
             0x35, 0x01, 0x01
         ];
 
-        let opt = super::Option::parse(&data).unwrap();
+        let opt = super::Option::parse(&data).unwrap().0;
 
         assert_eq!(opt.tag, 53);
         assert_eq!(opt.len, 1);
@@ -209,13 +1456,19 @@ mod tests {
             0x30, 0x31
         ];
 
-        let opt = super::Option::parse(&data).unwrap();
+        let opt = super::Option::parse(&data).unwrap().0;
 
         assert_eq!(opt.tag, 60);
         assert_eq!(opt.len, 32);
         assert_eq!(opt.value_as_string().unwrap().as_str(), "PXEClient:Arch:00000:UNDI:002001");
     }
 
+    #[test]
+    fn test_option_string_nul_trimmed() {
+        let opt = super::Option { tag: 12, len: 4, data: vec![0x68, 0x69, 0x00, 0x00] };
+        assert_eq!(opt.value_as_string().unwrap().as_str(), "hi");
+    }
+
     #[test]
     #[should_panic]
     fn test_frame_empty_invalid() {
@@ -271,7 +1524,10 @@ mod tests {
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+
+            // magic cookie
+            0x63, 0x82, 0x53, 0x63
         ];
 
         let frame = super::Frame::parse(&data).unwrap();
@@ -295,4 +1551,530 @@ mod tests {
         assert_eq!(frame.sname.len(), 64);
         assert_eq!(frame.file.len(), 128);
     }
+
+    #[test]
+    fn test_frame_header_fields_valid() {
+        // A BOOTREPLY header with the broadcast flag set
+        let mut data = vec![
+            0x02, 0x01, 0x06, 0x00, 0xde, 0xad, 0xbe, 0xef,
+            0x00, 0x00, 0x80, 0x00
+        ];
+
+        data.extend(vec![0; 16 + 16 + 64 + 128]);
+        data.extend(vec![0x63, 0x82, 0x53, 0x63]);
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.op, 0x02);
+        assert_eq!(frame.xid, 0xdeadbeef);
+        assert_eq!(frame.flags, 0x8000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_frame_bad_cookie_invalid() {
+        let mut data = vec![
+            0x01, 0x01, 0x06, 0x00, 0x6e, 0x86, 0x44, 0x4c,
+            0x00, 0x08, 0x00, 0x00
+        ];
+
+        data.extend(vec![0; 16 + 16 + 64 + 128]);
+        data.extend(vec![0x63, 0x82, 0x53, 0x00]);
+
+        super::Frame::parse(&data).unwrap();
+    }
+
+    #[test]
+    fn test_frame_option_lookup() {
+        let data = frame_with_options(&[
+            0x35, 0x01, 0x01,
+            0x0c, 0x04, 0x68, 0x6f, 0x73, 0x74,
+            0x37, 0x02, 0x01, 0x03,
+            0xff
+        ]);
+
+        let mut frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.option(53).unwrap().data, [0x01]);
+        assert_eq!(frame.option(12).unwrap().value_as_string().unwrap().as_str(), "host");
+        assert_eq!(frame.option(55).unwrap().data, [0x01, 0x03]);
+        assert!(frame.option(51).is_none());
+
+        assert!(frame.has_option(12));
+        assert!(!frame.has_option(61));
+
+        frame.option_mut(53).unwrap().data = vec![0x03];
+        assert_eq!(frame.option(53).unwrap().data, [0x03]);
+        assert!(frame.option_mut(61).is_none());
+    }
+
+    #[test]
+    fn test_frame_overload_file_valid() {
+        // Option overload (52) set to 1, the file field holds two more options
+        let mut data = frame_with_options(&[
+            0x35, 0x01, 0x02,
+            0x34, 0x01, 0x01,
+            0xff
+        ]);
+
+        data[108..120].copy_from_slice(&[
+            0x01, 0x04, 0xff, 0xff, 0xff, 0x00,
+            0x03, 0x04, 0x0a, 0x00, 0x00, 0x01
+        ]);
+        data[120] = 0xff;
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        // Merged into the options, option 52 and the file field are done with
+        assert_eq!(frame.options.len(), 3);
+        assert_eq!(frame.option(1).unwrap().data, [0xff, 0xff, 0xff, 0x00]);
+        assert_eq!(frame.option(3).unwrap().data, [0x0a, 0x00, 0x00, 0x01]);
+        assert!(frame.option(52).is_none());
+        assert!(frame.file.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_frame_overload_round_trip() {
+        // Option overload set to 3, file and sname both hold options
+        let mut data = frame_with_options(&[
+            0x35, 0x01, 0x05,
+            0x34, 0x01, 0x03,
+            0xff
+        ]);
+
+        data[108..121].copy_from_slice(&[
+            0x01, 0x04, 0xff, 0xff, 0xff, 0x00,
+            0x03, 0x04, 0x0a, 0x00, 0x00, 0x01,
+            0xff
+        ]);
+
+        data[44..58].copy_from_slice(&[0x0f, 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'o', b'r', b'g', 0xff]);
+
+        let frame = super::Frame::parse(&data).unwrap();
+        let bytes = frame.to_bytes().unwrap();
+        let again = super::Frame::parse(&bytes).unwrap();
+
+        // Every option is written once, in the main area
+        assert_eq!(again, frame);
+        assert_eq!(again.options.iter().map(|opt| opt.tag).collect::<Vec<u8>>(), [53, 1, 3, 15]);
+        assert_eq!(again.domain_name(), Some("example.org".to_string()));
+        assert!(bytes[44..236].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_frame_overload_sname_ignored() {
+        // Without option 52, bytes in sname are not options
+        let mut data = frame_with_options(&[0xff]);
+        data[44..50].copy_from_slice(&[0x01, 0x04, 0xff, 0xff, 0xff, 0x00]);
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert!(frame.options.is_empty());
+    }
+
+    #[test]
+    fn test_frame_concatenated_option() {
+        let data = frame_with_options(&[
+            0x2b, 0x03, 0x01, 0x02, 0x03,
+            0x35, 0x01, 0x01,
+            0x2b, 0x02, 0x04, 0x05,
+            0xff
+        ]);
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.concatenated_option(43).unwrap(), [0x01, 0x02, 0x03, 0x04, 0x05]);
+        assert_eq!(frame.concatenated_option(53).unwrap(), [0x01]);
+        assert!(frame.concatenated_option(60).is_none());
+    }
+
+    #[test]
+    fn test_frame_requested_parameters() {
+        let data = frame_with_options(&[0x37, 0x04, 0x01, 0x03, 0x06, 0x0f, 0xff]);
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.requested_parameters(), [1, 3, 6, 15]);
+
+        let frame = super::Frame::parse(&frame_with_options(&[0xff])).unwrap();
+        assert!(frame.requested_parameters().is_empty());
+    }
+
+    #[test]
+    fn test_frame_max_message_size() {
+        let frame = super::Frame::parse(&frame_with_options(&[0x39, 0x02, 0x05, 0xdc, 0xff])).unwrap();
+        assert_eq!(frame.max_message_size(), Some(1500));
+
+        let frame = super::Frame::parse(&frame_with_options(&[0x39, 0x01, 0x05, 0xff])).unwrap();
+        assert!(frame.max_message_size().is_none());
+    }
+
+    #[test]
+    fn test_frame_pxe_client() {
+        let mut options = vec![0x5d, 0x02, 0x00, 0x07, 0x5e, 0x03, 0x01, 0x03, 0x10, 0x61, 0x11, 0x00];
+        options.extend(&[0x4c, 0x4c, 0x45, 0x44, 0x00, 0x35, 0x4e, 0x10, 0x80, 0x47, 0xb2, 0xc0, 0x4f, 0x50, 0x31, 0x32]);
+        options.push(0xff);
+
+        let frame = super::Frame::parse(&frame_with_options(&options)).unwrap();
+
+        assert_eq!(frame.client_arch(), Some(::codes::ARCH_EFI_X86_64));
+        assert_eq!(frame.network_interface(), Some((1, 3, 16)));
+        assert_eq!(frame.client_uuid(), Some([0x4c, 0x4c, 0x45, 0x44, 0x00, 0x35, 0x4e, 0x10, 0x80, 0x47, 0xb2, 0xc0, 0x4f, 0x50, 0x31, 0x32]));
+
+        // Odd length architecture list, short interface id, UUID of another type
+        let data = frame_with_options(&[0x5d, 0x03, 0x00, 0x07, 0x00, 0x5e, 0x02, 0x01, 0x02, 0x61, 0x02, 0x01, 0x00, 0xff]);
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert!(frame.client_arch().is_none());
+        assert!(frame.network_interface().is_none());
+        assert!(frame.client_uuid().is_none());
+
+        // Several architectures, the first is preferred
+        let frame = super::Frame::parse(&frame_with_options(&[0x5d, 0x04, 0x00, 0x0b, 0x00, 0x07, 0xff])).unwrap();
+        assert_eq!(frame.client_arch(), Some(::codes::ARCH_EFI_ARM64));
+    }
+
+    #[test]
+    fn test_frame_subnet_mask_routers() {
+        let data = frame_with_options(&[
+            0x01, 0x04, 0xff, 0xff, 0xff, 0x00,
+            0x03, 0x08, 0xc0, 0xa8, 0x01, 0x01, 0xc0, 0xa8, 0x01, 0x02,
+            0xff
+        ]);
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.subnet_mask(), Some(super::Ipv4Addr::new(255, 255, 255, 0)));
+        assert_eq!(frame.routers(), [super::Ipv4Addr::new(192, 168, 1, 1), super::Ipv4Addr::new(192, 168, 1, 2)]);
+    }
+
+    #[test]
+    fn test_frame_subnet_mask_routers_malformed() {
+        let data = frame_with_options(&[
+            0x01, 0x03, 0xff, 0xff, 0xff,
+            0x03, 0x05, 0xc0, 0xa8, 0x01, 0x01, 0xc0,
+            0xff
+        ]);
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.subnet_mask(), None);
+        assert!(frame.routers().is_empty());
+    }
+
+    #[test]
+    fn test_frame_dns_domain() {
+        let mut options = vec![0x06, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x04, 0x04, 0x0f, 0x0c];
+        options.extend(b"example.com\0".iter());
+        options.push(0xff);
+
+        let frame = super::Frame::parse(&frame_with_options(&options)).unwrap();
+
+        assert_eq!(frame.dns_servers(), [super::Ipv4Addr::new(8, 8, 8, 8), super::Ipv4Addr::new(8, 8, 4, 4)]);
+        assert_eq!(frame.domain_name().unwrap().as_str(), "example.com");
+    }
+
+    #[test]
+    fn test_frame_dns_domain_malformed() {
+        let data = frame_with_options(&[
+            0x06, 0x06, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08,
+            0x0f, 0x02, 0xc3, 0x28,
+            0xff
+        ]);
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert!(frame.dns_servers().is_empty());
+        assert_eq!(frame.domain_name(), None);
+    }
+
+    #[test]
+    fn test_frame_client_identifier() {
+        let data = frame_with_options(&[0x3d, 0x07, 0x01, 0x52, 0x54, 0x01, 0x12, 0x34, 0x57, 0xff]);
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.client_identifier().unwrap(), [0x01, 0x52, 0x54, 0x01, 0x12, 0x34, 0x57]);
+        assert_eq!(frame.client_identifier_parts().unwrap(), (0x01, vec![0x52, 0x54, 0x01, 0x12, 0x34, 0x57]));
+        assert_eq!(frame.client_key(), [0x01, 0x52, 0x54, 0x01, 0x12, 0x34, 0x57]);
+
+        // Without option 61 the hardware address is the key
+        let frame = super::Frame::parse(&frame_with_options(&[0xff])).unwrap();
+
+        assert!(frame.client_identifier().is_none());
+        assert_eq!(frame.client_key(), [0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_frame_message_type() {
+        let mut frame = super::Frame::new(super::Opcode::Request, 0);
+        assert_eq!(frame.message_type(), None);
+
+        frame.add_option(super::Option { tag: 53, len: 1, data: vec![0x03] });
+        assert_eq!(frame.message_type(), Some(super::MessageType::Request));
+
+        frame.options[0].data = vec![0x2a];
+        assert_eq!(frame.message_type(), Some(super::MessageType::Unknown(42)));
+
+        frame.options[0].data = vec![];
+        assert_eq!(frame.message_type(), None);
+    }
+
+    #[test]
+    fn test_message_type_conversions() {
+        for v in 1..9 {
+            let t = super::MessageType::from(v);
+            assert_eq!(u8::from(t), v);
+        }
+
+        assert_eq!(super::MessageType::from(5), super::MessageType::Ack);
+        assert_eq!(super::MessageType::from(6), super::MessageType::Nak);
+    }
+
+    #[test]
+    fn test_hardware_type_conversions() {
+        assert_eq!(super::HardwareType::from(1), super::HardwareType::Ethernet);
+        assert_eq!(super::HardwareType::from(20), super::HardwareType::Serial);
+        assert_eq!(super::HardwareType::from(99), super::HardwareType::Other(99));
+
+        for v in 0..=255 {
+            assert_eq!(u8::from(super::HardwareType::from(v)), v);
+        }
+
+        let frame = super::Frame::parse(&frame_with_options(&[0xff])).unwrap();
+        assert_eq!(frame.hardware_type(), super::HardwareType::Ethernet);
+    }
+
+    #[test]
+    fn test_frame_addresses_valid() {
+        let mut data = frame_with_options(&[0xff]);
+        data[12..28].copy_from_slice(&[
+            10, 0, 0, 1, 10, 0, 0, 2, 10, 0, 0, 3, 10, 0, 0, 4
+        ]);
+
+        let mut frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.client_ip(), super::Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(frame.your_ip(), super::Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!(frame.server_ip(), super::Ipv4Addr::new(10, 0, 0, 3));
+        assert_eq!(frame.relay_ip(), super::Ipv4Addr::new(10, 0, 0, 4));
+
+        frame.giaddr = vec![10, 0];
+        assert_eq!(frame.relay_ip(), super::Ipv4Addr::new(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn test_frame_strings_valid() {
+        let mut data = frame_with_options(&[0xff]);
+        data[44..49].copy_from_slice(b"tftp0");
+        data[108..118].copy_from_slice(b"pxelinux.0");
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.server_name().unwrap().as_str(), "tftp0");
+        assert_eq!(frame.boot_file().unwrap().as_str(), "pxelinux.0");
+    }
+
+    #[test]
+    fn test_frame_hardware_address() {
+        let mut data = frame_with_options(&[0xff]);
+        data[28..34].copy_from_slice(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56]);
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.hardware_address(), [0x52, 0x54, 0x01, 0x12, 0x34, 0x56]);
+        assert_eq!(frame.client_mac_string().as_str(), "52:54:01:12:34:56");
+
+        // A 4 bytes hardware address, on another hardware type
+        data[1] = 0x20;
+        data[2] = 4;
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.hardware_address(), [0x52, 0x54, 0x01, 0x12]);
+        assert_eq!(frame.client_mac_string().as_str(), "52:54:01:12");
+    }
+
+    #[test]
+    fn test_frame_hardware_address_too_long() {
+        let mut data = frame_with_options(&[0xff]);
+        data[2] = 17;
+
+        let e = super::Frame::parse(&data).err().unwrap();
+        assert_eq!(e.kind(), super::ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn test_frame_options_valid() {
+        // Message type, client identifier and parameter request list
+        let data = frame_with_options(&[
+            0x35, 0x01, 0x01,
+            0x3d, 0x07, 0x01, 0x52, 0x54, 0x01, 0x12, 0x34, 0x56,
+            0x37, 0x03, 0x01, 0x03, 0x06,
+            0xff, 0x00
+        ]);
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.options.len(), 3);
+        assert_eq!(frame.options[0].tag, 53);
+        assert_eq!(frame.options[0].data, [0x01]);
+        assert_eq!(frame.options[1].tag, 61);
+        assert_eq!(frame.options[1].data, [0x01, 0x52, 0x54, 0x01, 0x12, 0x34, 0x56]);
+        assert_eq!(frame.options[2].tag, 55);
+        assert_eq!(frame.options[2].data, [0x01, 0x03, 0x06]);
+    }
+
+    #[test]
+    fn test_frame_only_end() {
+        let data = frame_with_options(&[0xff]);
+
+        assert!(super::Frame::parse(&data).unwrap().options.is_empty());
+        assert!(super::Frame::read_from(&mut ::std::io::Cursor::new(&data[..])).unwrap().options.is_empty());
+
+        let mut frame = super::Frame::parse(&frame_with_options(&[0x35, 0x01, 0x01, 0xff])).unwrap();
+        super::Frame::parse_into(&data, &mut frame).unwrap();
+        assert!(frame.options.is_empty());
+
+        assert!(super::parse_options(&[0xff]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_frame_pad_options_valid() {
+        // Pad bytes before, between and after real options, End without padding
+        let data = frame_with_options(&[
+            0x00, 0x00,
+            0x35, 0x01, 0x03,
+            0x00,
+            0x33, 0x04, 0x00, 0x01, 0x51, 0x80,
+            0x00, 0x00, 0x00,
+            0xff
+        ]);
+
+        let frame = super::Frame::parse(&data).unwrap();
+
+        assert_eq!(frame.options.len(), 2);
+        assert_eq!(frame.options[0].tag, 53);
+        assert_eq!(frame.options[0].data, [0x03]);
+        assert_eq!(frame.options[1].tag, 51);
+        assert_eq!(frame.options[1].data, [0x00, 0x01, 0x51, 0x80]);
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        use super::ErrorKind;
+
+        let e = super::Option::parse(&[0x35]).err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+
+        let e = super::Frame::parse(&[0x01, 0x01, 0x06, 0x00]).err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+
+        let mut data = frame_with_options(&[0xff]);
+        data[239] = 0x00;
+
+        let e = super::Frame::parse(&data).err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::InvalidMagicCookie);
+
+        let e = super::Frame::parse(&frame_with_options(&[0x35, 0x05, 0x01])).err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+
+        let opt = super::Option { tag: 51, len: 2, data: vec![0x0e, 0x10] };
+        assert_eq!(opt.as_u32().err().unwrap().kind(), ErrorKind::InvalidOption);
+
+        let opt = super::Option { tag: 12, len: 2, data: vec![0xc3, 0x28] };
+        assert_eq!(opt.value_as_string().err().unwrap().kind(), ErrorKind::InvalidUtf8);
+    }
+
+    #[test]
+    fn test_truncated_or_corrupted() {
+        use super::ErrorKind;
+
+        let data = frame_with_options(&[0x35, 0x01, 0x01, 0x0c, 0x04, 0x68, 0x6f, 0x73, 0x74, 0xff]);
+
+        // Cut inside the header or an option, more bytes would complete it
+        for len in &[0, 1, 100, 236, 238, 241, 244, 247] {
+            assert_eq!(super::Frame::parse(&data[..*len]).err().unwrap().kind(), ErrorKind::UnexpectedEof, "length {}", len);
+        }
+
+        // A complete frame with a wrong cookie is broken for good
+        let mut corrupted = data.clone();
+        corrupted[236] = 0x00;
+
+        assert_eq!(super::Frame::parse(&corrupted).err().unwrap().kind(), ErrorKind::InvalidMagicCookie);
+    }
+
+    extern crate proptest;
+
+    use self::proptest::prelude::*;
+    use self::proptest::collection::vec;
+
+    /*
+     * Any option that survives a round trip: Pad and End are not kept by the
+     * parser and Option Overload changes how the frame is read
+     */
+    fn arb_option() -> impl Strategy<Value = super::Option> {
+        (1u8..255, vec(any::<u8>(), 0..32))
+            .prop_filter("option overload", |&(tag, _)| tag != 52)
+            .prop_map(|(tag, data)| super::Option { tag, len: data.len() as u8, data })
+    }
+
+    fn arb_frame() -> impl Strategy<Value = super::Frame> {
+        let header = (1u8..3, any::<u8>(), 0u8..17, any::<u8>(), any::<u32>(), any::<u16>(), any::<u16>());
+        let addrs = (vec(any::<u8>(), 4), vec(any::<u8>(), 4), vec(any::<u8>(), 4), vec(any::<u8>(), 4));
+        let fields = (vec(any::<u8>(), 16), vec(any::<u8>(), 64), vec(any::<u8>(), 128));
+
+        (header, addrs, fields, vec(arb_option(), 0..8)).prop_map(|(h, a, mut f, options)| {
+            // Bytes past hlen are written as zeros
+            f.0[h.2 as usize..].iter_mut().for_each(|b| *b = 0);
+
+            super::Frame {
+                op: h.0,
+                htype: h.1,
+                hlen: h.2,
+                hops: h.3,
+                xid: h.4,
+                secs: h.5,
+                flags: h.6,
+                ciaddr: a.0,
+                yiaddr: a.1,
+                siaddr: a.2,
+                giaddr: a.3,
+                chaddr: f.0,
+                sname: f.1,
+                file: f.2,
+                options
+            }
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn test_option_round_trip(opt in arb_option()) {
+            let bytes = opt.to_bytes().unwrap();
+            prop_assert_eq!(super::Option::parse(&bytes).unwrap(), (opt, bytes.len()));
+        }
+
+        #[test]
+        fn test_frame_round_trip(frame in arb_frame()) {
+            let parsed = super::Frame::parse(&frame.to_bytes().unwrap()).unwrap();
+            prop_assert_eq!(parsed, frame);
+        }
+
+        /*
+         * Like the fuzz targets under fuzz/, with a valid header so the
+         * options get decoded
+         */
+        #[test]
+        fn test_parse_arbitrary_options(options in vec(any::<u8>(), 0..512)) {
+            if let Ok(frame) = super::Frame::parse(&frame_with_options(&options)) {
+                let _ = frame.client_fqdn();
+                let _ = frame.domain_search();
+                let _ = frame.classless_routes();
+                let _ = frame.user_class();
+                let _ = frame.vi_vendor_class();
+                let _ = frame.vi_vendor_specific();
+                let _ = frame.relay_agent_info();
+                let _ = frame.client_key();
+            }
+        }
+    }
 }
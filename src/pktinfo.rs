@@ -0,0 +1,259 @@
+/*
+ * Receiving datagrams with the interface they arrived on (IP_PKTINFO), so a
+ * server bound to 0.0.0.0 on a multi-homed host knows which link a broadcast
+ * came from and answers on it. Only Linux is supported: elsewhere enable
+ * fails and the server falls back to recv_from.
+ */
+
+use std::io;
+use std::net::{UdpSocket, SocketAddr, Ipv4Addr};
+
+#[cfg(target_os = "linux")]
+use std::ffi::CString;
+#[cfg(target_os = "linux")]
+use std::mem;
+#[cfg(target_os = "linux")]
+use std::net::SocketAddrV4;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+#[cfg(target_os = "linux")]
+use std::ptr;
+
+#[cfg(target_os = "linux")]
+use libc;
+
+/*
+ * Size of the ancillary data buffer, in u64 to keep it aligned for cmsghdr.
+ * Room for a single in_pktinfo message and then some.
+ */
+#[cfg(target_os = "linux")]
+const CONTROL_LEN: usize = 8;
+
+/*
+ * Where a datagram was received
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PacketInfo {
+    pub interface: u32,      // Index of the interface the datagram arrived on
+    pub local_ip: Ipv4Addr,  // Address of that interface, as a reply would be sent from
+    pub dst_ip: Ipv4Addr     // Destination of the datagram, broadcast for most client messages
+}
+
+/*
+ * Ask the kernel to report where datagrams are received
+ */
+#[cfg(target_os = "linux")]
+pub fn enable(socket: &UdpSocket) -> io::Result<()> {
+    let on: libc::c_int = 1;
+
+    let ret = unsafe {
+        libc::setsockopt(socket.as_raw_fd(), libc::IPPROTO_IP, libc::IP_PKTINFO,
+                         &on as *const libc::c_int as *const libc::c_void,
+                         mem::size_of_val(&on) as libc::socklen_t)
+    };
+
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn enable(_socket: &UdpSocket) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "IP_PKTINFO is only supported on Linux"))
+}
+
+/*
+ * Receive a datagram on an IPv4 socket with IP_PKTINFO enabled, along with
+ * its source and where it was received, if the kernel said so
+ */
+#[cfg(target_os = "linux")]
+pub fn recv(socket: &UdpSocket, buf: &mut [u8]) -> io::Result<(usize, SocketAddr, Option<PacketInfo>)> {
+    let mut addr: libc::sockaddr_in = unsafe { mem::zeroed() };
+    let mut control = [0u64; CONTROL_LEN];
+
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len()
+    };
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_name = &mut addr as *mut libc::sockaddr_in as *mut libc::c_void;
+    msg.msg_namelen = mem::size_of_val(&addr) as libc::socklen_t;
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = mem::size_of_val(&control) as _;
+
+    let len = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, 0) };
+    if len < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if addr.sin_family != libc::AF_INET as libc::sa_family_t {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Not an IPv4 datagram"));
+    }
+
+    let src = SocketAddrV4::new(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)), u16::from_be(addr.sin_port));
+    let mut info = None;
+
+    // Safe as long as the kernel filled msg, the macros check the lengths
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::IPPROTO_IP && (*cmsg).cmsg_type == libc::IP_PKTINFO {
+                let pktinfo = ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::in_pktinfo);
+
+                info = Some(PacketInfo {
+                    interface: pktinfo.ipi_ifindex as u32,
+                    local_ip: Ipv4Addr::from(u32::from_be(pktinfo.ipi_spec_dst.s_addr)),
+                    dst_ip: Ipv4Addr::from(u32::from_be(pktinfo.ipi_addr.s_addr))
+                });
+            }
+
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+
+    Ok((len as usize, SocketAddr::V4(src), info))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn recv(socket: &UdpSocket, buf: &mut [u8]) -> io::Result<(usize, SocketAddr, Option<PacketInfo>)> {
+    let (len, src) = socket.recv_from(buf)?;
+    Ok((len, src, None))
+}
+
+/*
+ * Send a datagram out of the interface a request was received on, from its
+ * address. Broadcasts would otherwise leave through whichever interface the
+ * routing table picks.
+ */
+#[cfg(target_os = "linux")]
+pub fn send(socket: &UdpSocket, buf: &[u8], dst: SocketAddr, info: &PacketInfo) -> io::Result<usize> {
+    let dst = match dst {
+        SocketAddr::V4(dst) => dst,
+        SocketAddr::V6(_) => return socket.send_to(buf, dst)
+    };
+
+    let mut addr: libc::sockaddr_in = unsafe { mem::zeroed() };
+    addr.sin_family = libc::AF_INET as libc::sa_family_t;
+    addr.sin_port = dst.port().to_be();
+    addr.sin_addr.s_addr = u32::from(*dst.ip()).to_be();
+
+    let mut control = [0u64; CONTROL_LEN];
+
+    let mut iov = libc::iovec {
+        iov_base: buf.as_ptr() as *mut libc::c_void,
+        iov_len: buf.len()
+    };
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_name = &mut addr as *mut libc::sockaddr_in as *mut libc::c_void;
+    msg.msg_namelen = mem::size_of_val(&addr) as libc::socklen_t;
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+
+    let pktinfo = libc::in_pktinfo {
+        ipi_ifindex: info.interface as libc::c_int,
+        ipi_spec_dst: libc::in_addr { s_addr: u32::from(info.local_ip).to_be() },
+        ipi_addr: libc::in_addr { s_addr: 0 }
+    };
+
+    // A single message, the buffer is large enough for it
+    let len = unsafe {
+        let data_len = mem::size_of::<libc::in_pktinfo>() as libc::c_uint;
+        msg.msg_controllen = libc::CMSG_SPACE(data_len) as _;
+
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::IPPROTO_IP;
+        (*cmsg).cmsg_type = libc::IP_PKTINFO;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(data_len) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut libc::in_pktinfo, pktinfo);
+
+        libc::sendmsg(socket.as_raw_fd(), &msg, 0)
+    };
+
+    if len < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(len as usize)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn send(socket: &UdpSocket, buf: &[u8], dst: SocketAddr, _info: &PacketInfo) -> io::Result<usize> {
+    socket.send_to(buf, dst)
+}
+
+/*
+ * Index of a network interface by name, as reported in PacketInfo
+ */
+#[cfg(target_os = "linux")]
+pub fn interface_index(name: &str) -> io::Result<u32> {
+    let cname = CString::new(name).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Interface name contains a NUL byte"))?;
+    let index = unsafe { libc::if_nametoindex(cname.as_ptr()) };
+
+    if index == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(index)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn interface_index(_name: &str) -> io::Result<u32> {
+    Err(io::Error::new(io::ErrorKind::Other, "Interface indexes are only supported on Linux"))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::net::{UdpSocket, Ipv4Addr};
+    use std::time::Duration;
+
+    use common::{Frame, MessageType, Opcode};
+    use writer::FrameBuilder;
+
+    #[test]
+    fn test_pktinfo_receive_send() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        super::enable(&server).unwrap();
+
+        let discover = FrameBuilder::new(Opcode::Request, 0x1234)
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+
+        client.send_to(&discover.to_bytes().unwrap(), server.local_addr().unwrap()).unwrap();
+
+        let mut buf = [0; 1500];
+        let (len, src, info) = super::recv(&server, &mut buf).unwrap();
+        let info = info.unwrap();
+
+        assert_eq!(src, client.local_addr().unwrap());
+        assert_eq!(Frame::parse(&buf[..len]).unwrap().message_type(), Some(MessageType::Discover));
+        assert!(info.interface != 0);
+        assert_eq!(info.local_ip, Ipv4Addr::new(127, 0, 0, 1));
+        assert_eq!(info.dst_ip, Ipv4Addr::new(127, 0, 0, 1));
+
+        // Answered out of the same interface
+        super::send(&server, &buf[..len], src, &info).unwrap();
+
+        let (len, from) = client.recv_from(&mut buf).unwrap();
+        assert_eq!(from, server.local_addr().unwrap());
+        assert_eq!(Frame::parse(&buf[..len]).unwrap().xid, 0x1234);
+    }
+
+    #[test]
+    fn test_interface_index() {
+        assert!(super::interface_index("lo").unwrap() != 0);
+        assert!(super::interface_index("bogus0").is_err());
+    }
+}
@@ -0,0 +1,309 @@
+/*
+ * Address pools - decide which IP address a client gets
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::net::Ipv4Addr;
+use std::option;
+
+/*
+ * Source of addresses handed out by the server
+ */
+pub trait AddressPool {
+    /*
+     * Get an address for a client, None if none is available. Clients are
+     * identified by their key (see Frame::client_key).
+     */
+    fn allocate(&mut self, mac: &[u8]) -> option::Option<Ipv4Addr>;
+
+    /*
+     * Get an address for a client on the network of link, the relay agent
+     * address (giaddr) or the server's own for directly connected clients.
+     * Pools serving a single network ignore it.
+     */
+    fn allocate_on(&mut self, mac: &[u8], link: Ipv4Addr) -> option::Option<Ipv4Addr> {
+        let _ = link;
+        self.allocate(mac)
+    }
+
+    /*
+     * Give back the address bound to a client
+     */
+    fn release(&mut self, mac: &[u8]);
+
+    /*
+     * Drop the address bound to a client and stop handing it out, after the
+     * client reported it in use by someone else (DHCPDECLINE)
+     */
+    fn decline(&mut self, mac: &[u8]);
+}
+
+/*
+ * A pool handing out addresses from an inclusive range
+ */
+pub struct RangePool {
+    start: u32,
+    end: u32,
+    reserved: option::Option<(u32, u32)>, // Network and broadcast addresses of the subnet, never handed out
+    bindings: HashMap<Vec<u8>, Ipv4Addr>, // Client key to IP address
+    bound: HashSet<Ipv4Addr>,             // Addresses of bindings, for constant time lookups
+    declined: HashSet<Ipv4Addr>           // Addresses never handed out again
+}
+
+impl RangePool {
+    /*
+     * Construct a pool of the addresses between start and end, inclusive
+     */
+    pub fn new(start: Ipv4Addr, end: Ipv4Addr) -> RangePool {
+        RangePool {
+            start: u32::from(start),
+            end: u32::from(end),
+            reserved: None,
+            bindings: HashMap::new(),
+            bound: HashSet::new(),
+            declined: HashSet::new()
+        }
+    }
+
+    /*
+     * Skip the network and broadcast addresses of the subnet of start with
+     * this mask, when the range covers them. /31 and /32 subnets have none
+     * (RFC 3021).
+     */
+    pub fn with_mask(mut self, mask: Ipv4Addr) -> RangePool {
+        let mask = u32::from(mask);
+
+        self.reserved = match mask.count_ones() {
+            31 | 32 => None,
+            _ => Some((self.start & mask, self.start | !mask))
+        };
+
+        self
+    }
+
+    /*
+     * Number of addresses left to hand out
+     */
+    pub fn available(&self) -> usize {
+        self.addresses().filter(|ip| !self.is_bound(ip)).count()
+    }
+
+    /*
+     * The addresses of the range that can be handed out, bound or not. An
+     * inclusive range stops at 255.255.255.255 without wrapping around.
+     */
+    fn addresses(&self) -> impl Iterator<Item = Ipv4Addr> + '_ {
+        (self.start..=self.end)
+            .filter(move |&ip| match self.reserved {
+                Some((network, broadcast)) => ip != network && ip != broadcast,
+                None => true
+            })
+            .map(Ipv4Addr::from)
+            .filter(move |ip| !self.declined.contains(ip))
+    }
+
+    /*
+     * Whether an address is bound to a client
+     */
+    fn is_bound(&self, ip: &Ipv4Addr) -> bool {
+        self.bound.contains(ip)
+    }
+
+    /*
+     * Get the address currently bound to a client, if any
+     */
+    pub fn binding(&self, mac: &[u8]) -> option::Option<Ipv4Addr> {
+        self.bindings.get(mac).cloned()
+    }
+}
+
+impl AddressPool for RangePool {
+    fn allocate(&mut self, mac: &[u8]) -> option::Option<Ipv4Addr> {
+        if let Some(ip) = self.binding(mac) {
+            return Some(ip);
+        }
+
+        let ip = self.addresses().find(|ip| !self.is_bound(ip))?;
+
+        self.bindings.insert(mac.to_vec(), ip);
+        self.bound.insert(ip);
+        Some(ip)
+    }
+
+    fn release(&mut self, mac: &[u8]) {
+        if let Some(ip) = self.bindings.remove(mac) {
+            self.bound.remove(&ip);
+        }
+    }
+
+    fn decline(&mut self, mac: &[u8]) {
+        if let Some(ip) = self.bindings.remove(mac) {
+            self.bound.remove(&ip);
+            self.declined.insert(ip);
+        }
+    }
+}
+
+/*
+ * One RangePool per subnet, chosen by the network a client is on
+ */
+#[derive(Default)]
+pub struct SubnetPools {
+    subnets: Vec<(u32, u32, RangePool)> // Network, mask and pool
+}
+
+impl SubnetPools {
+    /*
+     * Construct an empty set of subnets
+     */
+    pub fn new() -> SubnetPools {
+        SubnetPools::default()
+    }
+
+    /*
+     * Serve a subnet from a pool
+     */
+    pub fn add_subnet(&mut self, network: Ipv4Addr, mask: Ipv4Addr, pool: RangePool) {
+        let mask = u32::from(mask);
+        self.subnets.push((u32::from(network) & mask, mask, pool));
+    }
+
+    /*
+     * Get the pool serving the subnet an address belongs to
+     */
+    pub fn subnet(&self, ip: Ipv4Addr) -> option::Option<&RangePool> {
+        let ip = u32::from(ip);
+
+        self.subnets.iter()
+            .find(|&&(network, mask, _)| ip & mask == network)
+            .map(|(_, _, pool)| pool)
+    }
+}
+
+impl AddressPool for SubnetPools {
+    /*
+     * Without a link, keep the address a client already has or use the
+     * first subnet
+     */
+    fn allocate(&mut self, mac: &[u8]) -> option::Option<Ipv4Addr> {
+        if let Some(ip) = self.subnets.iter().filter_map(|(_, _, pool)| pool.binding(mac)).next() {
+            return Some(ip);
+        }
+
+        self.subnets.first_mut()?.2.allocate(mac)
+    }
+
+    fn allocate_on(&mut self, mac: &[u8], link: Ipv4Addr) -> option::Option<Ipv4Addr> {
+        let link = u32::from(link);
+        let index = self.subnets.iter().position(|&(network, mask, _)| link & mask == network)?;
+
+        // A client moving to another subnet gives back its old address
+        for (i, (_, _, pool)) in self.subnets.iter_mut().enumerate() {
+            if i != index {
+                pool.release(mac);
+            }
+        }
+
+        self.subnets[index].2.allocate(mac)
+    }
+
+    fn release(&mut self, mac: &[u8]) {
+        for (_, _, pool) in &mut self.subnets {
+            pool.release(mac);
+        }
+    }
+
+    fn decline(&mut self, mac: &[u8]) {
+        for (_, _, pool) in &mut self.subnets {
+            pool.decline(mac);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::{AddressPool, RangePool, SubnetPools};
+
+    #[test]
+    fn test_range_pool_bindings() {
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 10), Ipv4Addr::new(10, 0, 0, 20));
+
+        let a = pool.allocate(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56]).unwrap();
+        let b = pool.allocate(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x57]).unwrap();
+
+        assert_eq!(a, Ipv4Addr::new(10, 0, 0, 10));
+        assert_eq!(b, Ipv4Addr::new(10, 0, 0, 11));
+        assert_eq!(pool.allocate(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56]), Some(a));
+    }
+
+    #[test]
+    fn test_range_pool_release() {
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 10), Ipv4Addr::new(10, 0, 0, 10));
+
+        assert!(pool.allocate(&[1]).is_some());
+        assert!(pool.allocate(&[2]).is_none());
+
+        pool.release(&[1]);
+
+        assert_eq!(pool.allocate(&[2]), Some(Ipv4Addr::new(10, 0, 0, 10)));
+        assert_eq!(pool.binding(&[1]), None);
+    }
+
+    #[test]
+    fn test_range_pool_decline() {
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 10), Ipv4Addr::new(10, 0, 0, 11));
+
+        assert_eq!(pool.allocate(&[1]), Some(Ipv4Addr::new(10, 0, 0, 10)));
+
+        pool.decline(&[1]);
+
+        assert_eq!(pool.binding(&[1]), None);
+        assert_eq!(pool.allocate(&[1]), Some(Ipv4Addr::new(10, 0, 0, 11)));
+        assert!(pool.allocate(&[2]).is_none());
+    }
+
+    #[test]
+    fn test_range_pool_exhaustion() {
+        // A /30 has two usable addresses
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 0), Ipv4Addr::new(10, 0, 0, 3))
+            .with_mask(Ipv4Addr::new(255, 255, 255, 252));
+
+        assert_eq!(pool.available(), 2);
+        assert_eq!(pool.allocate(&[1]), Some(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(pool.allocate(&[2]), Some(Ipv4Addr::new(10, 0, 0, 2)));
+        assert_eq!(pool.available(), 0);
+        assert_eq!(pool.allocate(&[3]), None);
+
+        pool.release(&[1]);
+        assert_eq!(pool.available(), 1);
+
+        // Up to the last address without wrapping around
+        let mut pool = RangePool::new(Ipv4Addr::new(255, 255, 255, 254), Ipv4Addr::new(255, 255, 255, 255));
+        assert_eq!(pool.allocate(&[1]), Some(Ipv4Addr::new(255, 255, 255, 254)));
+        assert_eq!(pool.allocate(&[2]), Some(Ipv4Addr::new(255, 255, 255, 255)));
+        assert_eq!(pool.allocate(&[3]), None);
+
+        // Point to point links use both addresses
+        let pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 0), Ipv4Addr::new(10, 0, 0, 1))
+            .with_mask(Ipv4Addr::new(255, 255, 255, 254));
+        assert_eq!(pool.available(), 2);
+    }
+
+    #[test]
+    fn test_subnet_pools() {
+        let mut pools = SubnetPools::new();
+        pools.add_subnet(Ipv4Addr::new(10, 0, 1, 0), Ipv4Addr::new(255, 255, 255, 0), RangePool::new(Ipv4Addr::new(10, 0, 1, 10), Ipv4Addr::new(10, 0, 1, 20)));
+        pools.add_subnet(Ipv4Addr::new(10, 0, 2, 0), Ipv4Addr::new(255, 255, 255, 0), RangePool::new(Ipv4Addr::new(10, 0, 2, 10), Ipv4Addr::new(10, 0, 2, 20)));
+
+        assert_eq!(pools.allocate_on(&[1], Ipv4Addr::new(10, 0, 1, 1)), Some(Ipv4Addr::new(10, 0, 1, 10)));
+        assert_eq!(pools.allocate_on(&[2], Ipv4Addr::new(10, 0, 2, 1)), Some(Ipv4Addr::new(10, 0, 2, 10)));
+        assert_eq!(pools.allocate_on(&[3], Ipv4Addr::new(10, 0, 3, 1)), None);
+        assert_eq!(pools.allocate(&[2]), Some(Ipv4Addr::new(10, 0, 2, 10)));
+
+        // Moving to the other subnet frees the old address
+        assert_eq!(pools.allocate_on(&[1], Ipv4Addr::new(10, 0, 2, 1)), Some(Ipv4Addr::new(10, 0, 2, 11)));
+        assert_eq!(pools.subnet(Ipv4Addr::new(10, 0, 1, 10)).unwrap().binding(&[1]), None);
+    }
+}
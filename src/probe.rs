@@ -0,0 +1,251 @@
+/*
+ * Conflict detection, probing candidate addresses before offering them
+ * (RFC 2131 section 2.2)
+ */
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::option;
+use std::time::{Duration, Instant};
+
+use socket2::{Socket, Domain, Type, Protocol};
+
+use pool::AddressPool;
+
+/*
+ * ICMP message types
+ */
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+
+/*
+ * Identifier of our echo requests
+ */
+const ICMP_ECHO_ID: u16 = 0xdc;
+
+/*
+ * Something checking whether an address is already in use
+ */
+pub trait Probe {
+    /*
+     * Whether a host answered at the address
+     */
+    fn in_use(&mut self, ip: Ipv4Addr) -> bool;
+}
+
+/*
+ * Probe sending an ICMP echo request and waiting for the reply. Raw sockets
+ * need privileges (CAP_NET_RAW on Linux): when the socket cannot be opened,
+ * every address is assumed free.
+ */
+pub struct IcmpProbe {
+    timeout: Duration,
+    seq: u16
+}
+
+impl IcmpProbe {
+    /*
+     * Construct a probe waiting at most timeout for each reply
+     */
+    pub fn new(timeout: Duration) -> IcmpProbe {
+        IcmpProbe {
+            timeout,
+            seq: 0
+        }
+    }
+
+    /*
+     * Send an echo request and wait for its reply, errors mean no reply
+     */
+    fn ping(&self, ip: Ipv4Addr, seq: u16) -> ::std::io::Result<bool> {
+        let socket = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))?;
+        socket.send_to(&echo_request(seq), &SocketAddrV4::new(ip, 0).into())?;
+
+        let deadline = Instant::now() + self.timeout;
+        let mut buf = [0; 1500];
+
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+
+            socket.set_read_timeout(Some(deadline - now))?;
+            let len = (&socket).read(&mut buf)?;
+
+            if is_echo_reply(&buf[..len], ip, seq) {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+impl Probe for IcmpProbe {
+    fn in_use(&mut self, ip: Ipv4Addr) -> bool {
+        self.seq = self.seq.wrapping_add(1);
+        self.ping(ip, self.seq).unwrap_or(false)
+    }
+}
+
+/*
+ * Build an ICMP echo request without payload
+ */
+fn echo_request(seq: u16) -> [u8; 8] {
+    let mut packet = [ICMP_ECHO_REQUEST, 0, 0, 0, (ICMP_ECHO_ID >> 8) as u8, ICMP_ECHO_ID as u8, (seq >> 8) as u8, seq as u8];
+    let sum = checksum(&packet);
+
+    packet[2] = (sum >> 8) as u8;
+    packet[3] = sum as u8;
+    packet
+}
+
+/*
+ * Check whether a datagram read from a raw socket, IPv4 header included, is
+ * the reply to our echo request
+ */
+fn is_echo_reply(data: &[u8], ip: Ipv4Addr, seq: u16) -> bool {
+    if data.len() < 20 {
+        return false;
+    }
+
+    let header_len = ((data[0] & 0x0f) as usize) * 4;
+    let icmp = &data[header_len.min(data.len())..];
+
+    icmp.len() >= 8 &&
+        data[12..16] == ip.octets() &&
+        icmp[0] == ICMP_ECHO_REPLY &&
+        icmp[4..6] == [(ICMP_ECHO_ID >> 8) as u8, ICMP_ECHO_ID as u8] &&
+        icmp[6..8] == [(seq >> 8) as u8, seq as u8]
+}
+
+/*
+ * Internet checksum (RFC 1071)
+ */
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum = data.chunks(2)
+        .map(|c| ((c[0] as u32) << 8) | *c.get(1).unwrap_or(&0) as u32)
+        .sum::<u32>();
+
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+/*
+ * A pool probing each new address before handing it out. Addresses found in
+ * use are declined, so the inner pool never offers them again.
+ */
+pub struct ProbingPool<P, Q> {
+    pool: P,
+    probe: Q,
+    probed: HashMap<Vec<u8>, Ipv4Addr> // Addresses already checked for a client
+}
+
+impl<P: AddressPool, Q: Probe> ProbingPool<P, Q> {
+    /*
+     * Wrap a pool, checking its addresses with a probe
+     */
+    pub fn new(pool: P, probe: Q) -> ProbingPool<P, Q> {
+        ProbingPool {
+            pool,
+            probe,
+            probed: HashMap::new()
+        }
+    }
+
+    /*
+     * Allocate from the inner pool until an address is found free
+     */
+    fn allocate_with<F: FnMut(&mut P) -> option::Option<Ipv4Addr>>(&mut self, mac: &[u8], mut allocate: F) -> option::Option<Ipv4Addr> {
+        loop {
+            let ip = allocate(&mut self.pool)?;
+
+            // Once bound the client itself answers, do not probe it again
+            if self.probed.get(mac) == Some(&ip) {
+                return Some(ip);
+            }
+
+            if !self.probe.in_use(ip) {
+                self.probed.insert(mac.to_vec(), ip);
+                return Some(ip);
+            }
+
+            self.pool.decline(mac);
+        }
+    }
+
+    /*
+     * Get the wrapped pool back
+     */
+    pub fn into_inner(self) -> P {
+        self.pool
+    }
+}
+
+impl<P: AddressPool, Q: Probe> AddressPool for ProbingPool<P, Q> {
+    fn allocate(&mut self, mac: &[u8]) -> option::Option<Ipv4Addr> {
+        self.allocate_with(mac, |pool| pool.allocate(mac))
+    }
+
+    fn allocate_on(&mut self, mac: &[u8], link: Ipv4Addr) -> option::Option<Ipv4Addr> {
+        self.allocate_with(mac, |pool| pool.allocate_on(mac, link))
+    }
+
+    fn release(&mut self, mac: &[u8]) {
+        self.probed.remove(mac);
+        self.pool.release(mac);
+    }
+
+    fn decline(&mut self, mac: &[u8]) {
+        self.probed.remove(mac);
+        self.pool.decline(mac);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use pool::{AddressPool, RangePool};
+    use super::{Probe, ProbingPool};
+
+    /*
+     * Fake probe finding a single address in use, counting its calls
+     */
+    struct FakeProbe {
+        used: Ipv4Addr,
+        calls: usize
+    }
+
+    impl Probe for FakeProbe {
+        fn in_use(&mut self, ip: Ipv4Addr) -> bool {
+            self.calls += 1;
+            ip == self.used
+        }
+    }
+
+    #[test]
+    fn test_probing_pool_skips_used() {
+        let probe = FakeProbe { used: Ipv4Addr::new(10, 0, 0, 10), calls: 0 };
+        let mut pool = ProbingPool::new(RangePool::new(Ipv4Addr::new(10, 0, 0, 10), Ipv4Addr::new(10, 0, 0, 11)), probe);
+
+        assert_eq!(pool.allocate(&[1]), Some(Ipv4Addr::new(10, 0, 0, 11)));
+        assert_eq!(pool.allocate(&[1]), Some(Ipv4Addr::new(10, 0, 0, 11)));
+        assert_eq!(pool.probe.calls, 2);
+
+        // The address in use was declined, nothing is left
+        assert!(pool.allocate(&[2]).is_none());
+        assert_eq!(pool.into_inner().binding(&[1]), Some(Ipv4Addr::new(10, 0, 0, 11)));
+    }
+
+    #[test]
+    fn test_echo_request_checksum() {
+        let packet = super::echo_request(1);
+
+        assert_eq!(packet[..2], [8, 0]);
+        assert_eq!(super::checksum(&packet), 0);
+    }
+}
@@ -0,0 +1,308 @@
+/*
+ * DHCP relay agent (RFC 1542): forwards client broadcasts to a server on
+ * another link and the server's replies back to the clients
+ */
+
+use std::net::{UdpSocket, SocketAddr, Ipv4Addr};
+use std::option;
+
+use common::{Result, Error, ErrorKind, Frame, MessageType, Opcode};
+use server::{self, DEFAULT_RECV_BUFFER_SIZE};
+use pktinfo::{self, PacketInfo};
+
+/*
+ * Requests that already went through this many relays are dropped, the
+ * limit recommended by RFC 1542
+ */
+pub const DEFAULT_MAX_HOPS: u8 = 16;
+
+/*
+ * Relay settings
+ */
+#[derive(Clone, Debug)]
+pub struct RelayConfig {
+    pub bind_addr: SocketAddr,             // Address to listen on, for both client requests and server replies
+    pub relay_ip: Ipv4Addr,                // Address on the clients' link sent as giaddr, the one requests arrive on if unspecified
+    pub upstream: SocketAddr,              // Server requests are forwarded to
+    pub max_hops: u8,                      // Requests with this many hops or more are dropped
+    pub interface: option::Option<String>, // Only relay requests from this interface and send replies out of it (Linux only)
+    pub recv_buffer_size: usize            // Frames filling the whole buffer are dropped as truncated
+}
+
+impl RelayConfig {
+    /*
+     * Relay to a server, the other settings at their defaults. There is no
+     * default server: a relay broadcasting upstream would hear its own
+     * requests again.
+     */
+    pub fn new(upstream: SocketAddr) -> RelayConfig {
+        RelayConfig {
+            bind_addr: SocketAddr::from((Ipv4Addr::new(0, 0, 0, 0), 67)),
+            relay_ip: Ipv4Addr::new(0, 0, 0, 0),
+            upstream,
+            max_hops: DEFAULT_MAX_HOPS,
+            interface: None,
+            recv_buffer_size: DEFAULT_RECV_BUFFER_SIZE
+        }
+    }
+}
+
+/*
+ * Relay the requests of the clients on an interface to a server, and its
+ * replies back, forever
+ */
+pub fn relay(client_iface: &str, upstream: Ipv4Addr) -> Result<()> {
+    relay_with(&RelayConfig {
+        interface: Some(client_iface.to_string()),
+        ..RelayConfig::new(SocketAddr::from((upstream, 67)))
+    })
+}
+
+/*
+ * Bind to the configured address and relay frames forever. The client link
+ * is the configured interface, or the one holding relay_ip: one of them is
+ * required.
+ */
+pub fn relay_with(config: &RelayConfig) -> Result<()> {
+    if config.interface.is_none() && config.relay_ip.is_unspecified() {
+        return Err(Error::with_kind(ErrorKind::InvalidValue, "A relay needs a client interface or relay_ip"));
+    }
+
+    let socket = server::bind(config.bind_addr)?;
+
+    // The ingress interface tells client requests from other traffic and
+    // gives the address to relay them as
+    let with_pktinfo = socket.local_addr()?.is_ipv4() && pktinfo::enable(&socket).is_ok();
+
+    let client_iface = match config.interface {
+        Some(ref name) if with_pktinfo => Some(pktinfo::interface_index(name)?),
+        Some(ref name) => return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Cannot relay for interface {}: IP_PKTINFO is only supported on Linux", name))),
+        None => None
+    };
+
+    let mut buf = vec![0; config.recv_buffer_size];
+
+    loop {
+        let (len, src, info) = match server::receive(&socket, &mut buf, with_pktinfo)? {
+            Some(received) => received,
+            None => continue
+        };
+
+        let frame = match Frame::parse(&buf[..len]) {
+            Ok(frame) => frame,
+            Err(e) => {
+                error!("Failed to parse frame from {}: {}", src, e);
+                continue;
+            }
+        };
+
+        match frame.opcode() {
+            Opcode::Request => relay_request(&socket, &frame, config, client_iface, info),
+            Opcode::Reply => relay_reply(&socket, &frame, client_iface),
+            Opcode::Unknown(_) => {}
+        }
+    }
+}
+
+/*
+ * Forward a client request to the upstream server, unless it did not come
+ * from the client link. Where requests arrive is only known with IP_PKTINFO.
+ */
+fn relay_request(socket: &UdpSocket, frame: &Frame, config: &RelayConfig, client_iface: option::Option<u32>, info: option::Option<PacketInfo>) {
+    let from_clients = match (client_iface, info) {
+        (Some(index), Some(info)) => info.interface == index,
+        (None, Some(info)) => info.local_ip == config.relay_ip,
+        (_, None) => true
+    };
+
+    if !from_clients {
+        return;
+    }
+
+    let relay_ip = match info {
+        _ if !config.relay_ip.is_unspecified() => config.relay_ip,
+        Some(info) => info.local_ip,
+        None => {
+            warn!("Dropping request from {}: no relay address configured", frame.client_mac_string());
+            return;
+        }
+    };
+
+    let forwarded = match forward_request(frame, relay_ip, config.max_hops) {
+        Some(forwarded) => forwarded,
+        None => {
+            warn!("Dropping request from {}: {} hops, relayed by {}", frame.client_mac_string(), frame.hops, frame.relay_ip());
+            return;
+        }
+    };
+
+    debug!("Relaying {:?} from {} to {}", frame.message_type(), frame.client_mac_string(), config.upstream);
+    send(socket, &forwarded, config.upstream, None);
+}
+
+/*
+ * Forward a server reply to the client it is meant for, out of the client
+ * interface if one is configured
+ */
+fn relay_reply(socket: &UdpSocket, frame: &Frame, client_iface: option::Option<u32>) {
+    let (reply, dst) = match forward_reply(frame) {
+        Some(forwarded) => forwarded,
+        None => return
+    };
+
+    let info = client_iface.map(|index| PacketInfo {
+        interface: index,
+        local_ip: frame.relay_ip(),
+        dst_ip: Ipv4Addr::new(0, 0, 0, 0)
+    });
+
+    debug!("Relaying {:?} for {} to {}", frame.message_type(), frame.client_mac_string(), dst);
+    send(socket, &reply, dst, info);
+}
+
+/*
+ * Serialize and send a relayed frame, logging failures
+ */
+fn send(socket: &UdpSocket, frame: &Frame, dst: SocketAddr, info: option::Option<PacketInfo>) {
+    let bytes = match frame.to_bytes() {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to serialize frame for {}: {}", dst, e);
+            return;
+        }
+    };
+
+    let sent = match info {
+        Some(ref info) => pktinfo::send(socket, &bytes, dst, info),
+        None => socket.send_to(&bytes, dst)
+    };
+
+    if let Err(e) = sent {
+        error!("Failed to relay frame to {}: {}", dst, e);
+    }
+}
+
+/*
+ * The request to send upstream: giaddr set to the relay address unless a
+ * relay closer to the client already did, and hops incremented. None for
+ * frames that are not requests, that have gone through max_hops relays or
+ * that this relay already forwarded.
+ */
+pub fn forward_request(frame: &Frame, relay_ip: Ipv4Addr, max_hops: u8) -> option::Option<Frame> {
+    if frame.opcode() != Opcode::Request || frame.hops >= max_hops || frame.relay_ip() == relay_ip {
+        return None;
+    }
+
+    let mut forwarded = frame.clone();
+    forwarded.hops += 1;
+
+    if forwarded.relay_ip().is_unspecified() {
+        forwarded.set_relay_ip(relay_ip);
+    }
+
+    Some(forwarded)
+}
+
+/*
+ * The reply to send to the client, with its destination. Clients without an
+ * address cannot answer ARP, so they are only unicast to when ciaddr is set
+ * and broadcast to otherwise. None for frames that are not replies or that
+ * were not relayed.
+ */
+pub fn forward_reply(frame: &Frame) -> option::Option<(Frame, SocketAddr)> {
+    if frame.opcode() != Opcode::Reply || frame.relay_ip().is_unspecified() {
+        return None;
+    }
+
+    let client = frame.client_ip();
+
+    let dst = if !client.is_unspecified() && frame.message_type() != Some(MessageType::Nak) {
+        SocketAddr::from((client, 68))
+    } else {
+        SocketAddr::from((Ipv4Addr::new(255, 255, 255, 255), 68))
+    };
+
+    Some((frame.clone(), dst))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{SocketAddr, Ipv4Addr};
+
+    use common::{Frame, MessageType, Opcode};
+    use writer::FrameBuilder;
+
+    fn discover() -> Frame {
+        FrameBuilder::new(Opcode::Request, 0x1234)
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_forward_request() {
+        let relay = Ipv4Addr::new(10, 0, 0, 1);
+        let forwarded = super::forward_request(&discover(), relay, super::DEFAULT_MAX_HOPS).unwrap();
+
+        assert_eq!(forwarded.relay_ip(), relay);
+        assert_eq!(forwarded.hops, 1);
+        assert_eq!(forwarded.xid(), 0x1234);
+        assert_eq!(forwarded.message_type(), Some(MessageType::Discover));
+
+        // A relay further from the client keeps the first giaddr
+        let again = super::forward_request(&forwarded, Ipv4Addr::new(10, 1, 0, 1), super::DEFAULT_MAX_HOPS).unwrap();
+        assert_eq!(again.relay_ip(), relay);
+        assert_eq!(again.hops, 2);
+
+        // Heard back, e.g. broadcast upstream: not forwarded twice
+        assert!(super::forward_request(&forwarded, relay, super::DEFAULT_MAX_HOPS).is_none());
+    }
+
+    #[test]
+    fn test_relay_config() {
+        let upstream = SocketAddr::from((Ipv4Addr::new(10, 1, 0, 2), 67));
+        let mut config = super::RelayConfig::new(upstream);
+
+        assert_eq!(config.upstream, upstream);
+        assert_eq!(config.max_hops, super::DEFAULT_MAX_HOPS);
+
+        // Either the interface or the address tells where clients are
+        config.bind_addr = SocketAddr::from((Ipv4Addr::new(127, 0, 0, 1), 0));
+        let e = super::relay_with(&config).unwrap_err();
+        assert_eq!(e.kind(), ::common::ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn test_forward_request_max_hops() {
+        let mut frame = discover();
+        frame.hops = 3;
+
+        assert_eq!(super::forward_request(&frame, Ipv4Addr::new(10, 0, 0, 1), 4).unwrap().hops, 4);
+
+        frame.hops = 4;
+        assert!(super::forward_request(&frame, Ipv4Addr::new(10, 0, 0, 1), 4).is_none());
+    }
+
+    #[test]
+    fn test_forward_reply() {
+        let mut offer = FrameBuilder::new(Opcode::Reply, 0x1234)
+            .message_type(MessageType::Offer)
+            .your_ip(Ipv4Addr::new(10, 0, 0, 10))
+            .build()
+            .unwrap();
+
+        // Not relayed, and requests are not replies
+        assert!(super::forward_reply(&offer).is_none());
+        assert!(super::forward_reply(&discover()).is_none());
+
+        offer.set_relay_ip(Ipv4Addr::new(10, 0, 0, 1));
+        let (reply, dst) = super::forward_reply(&offer).unwrap();
+        assert_eq!(reply.your_ip(), Ipv4Addr::new(10, 0, 0, 10));
+        assert_eq!(dst, SocketAddr::from((Ipv4Addr::new(255, 255, 255, 255), 68)));
+
+        // Renewing clients have an address to be unicast to
+        offer.set_client_ip(Ipv4Addr::new(10, 0, 0, 10));
+        let (_, dst) = super::forward_reply(&offer).unwrap();
+        assert_eq!(dst, SocketAddr::from((Ipv4Addr::new(10, 0, 0, 10), 68)));
+    }
+}
@@ -0,0 +1,112 @@
+/*
+ * Serde representations of frame fields
+ */
+
+/*
+ * Byte buffers as lowercase hex strings
+ */
+pub mod hex {
+    use serde::{Serializer, Deserializer, Deserialize};
+    use serde::de::Error;
+
+    pub fn serialize<S: Serializer>(data: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        let hex: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+        s.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(d)?;
+
+        if !hex.len().is_multiple_of(2) {
+            return Err(D::Error::custom("odd number of hex digits"));
+        }
+
+        // Decoded from the bytes, slicing the string could split a character
+        hex.as_bytes().chunks(2)
+            .map(|pair| match (digit(pair[0]), digit(pair[1])) {
+                (Some(high), Some(low)) => Ok(high << 4 | low),
+                _ => Err(D::Error::custom(format!("invalid hex string {:?}", hex)))
+            })
+            .collect()
+    }
+
+    /*
+     * Value of an ASCII hex digit
+     */
+    fn digit(c: u8) -> Option<u8> {
+        (c as char).to_digit(16).map(|d| d as u8)
+    }
+}
+
+/*
+ * 4 bytes addresses as dotted quad strings
+ */
+pub mod ipv4 {
+    use std::net::Ipv4Addr;
+
+    use serde::{Serializer, Deserializer, Deserialize};
+    use serde::de::Error;
+
+    pub fn serialize<S: Serializer>(data: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        if data.len() != 4 {
+            return Err(::serde::ser::Error::custom("address is not 4 bytes long"));
+        }
+
+        s.collect_str(&Ipv4Addr::new(data[0], data[1], data[2], data[3]))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(d)?;
+
+        match s.parse::<Ipv4Addr>() {
+            Ok(ip) => Ok(ip.octets().to_vec()),
+            Err(e) => Err(D::Error::custom(e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use common::{Frame, MessageType, Opcode};
+    use writer::FrameBuilder;
+
+    #[test]
+    fn test_frame_json_round_trip() {
+        let frame = FrameBuilder::new(Opcode::Request, 0x6e86444c)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+
+        let frame = Frame::parse(&frame.to_bytes().unwrap()).unwrap();
+        let json = serde_json::to_string(&frame).unwrap();
+
+        assert!(json.contains("\"ciaddr\":\"0.0.0.0\""));
+        assert!(json.contains("\"chaddr\":\"52540112345600000000000000000000\""));
+        assert!(json.contains("\"data\":\"01\""));
+
+        let decoded: Frame = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded.to_bytes().unwrap(), frame.to_bytes().unwrap());
+        assert_eq!(decoded.message_type(), Some(MessageType::Discover));
+    }
+
+    #[test]
+    fn test_hex_invalid() {
+        let json = serde_json::to_string(&Frame::new(Opcode::Request, 42)).unwrap();
+        assert!(json.contains("\"chaddr\":\"00000000000000000000000000000000\""));
+
+        // "aéa" is 4 bytes long but not hex, and neither is a sign
+        for bad in &["a\u{e9}a", "+f", "0g", "abc"] {
+            let json = json.replace("\"chaddr\":\"00000000000000000000000000000000\"", &format!("\"chaddr\":\"{}\"", bad));
+            assert!(serde_json::from_str::<Frame>(&json).is_err());
+        }
+    }
+
+    #[test]
+    fn test_message_type_json() {
+        assert_eq!(serde_json::to_string(&MessageType::Offer).unwrap().as_str(), "\"Offer\"");
+    }
+}
@@ -0,0 +1,1433 @@
+/*
+ * DHCP server
+ */
+
+use std::io;
+use std::net::{UdpSocket, SocketAddr, Ipv4Addr, ToSocketAddrs};
+use std::option;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use socket2::{Socket, Domain, Type, Protocol};
+
+use common::{Result, Error, ErrorKind, Option, Frame, MessageType, Opcode, RequestState};
+use codes::{BOOTP_MIN_LEN, OPTION_DHCP_MSG_TYPE, OPTION_PARAMETER_LIST, OPTION_SUBNET_SELECTION, OPTION_SUBNET_MASK, OPTION_ROUTER, OPTION_DOMAIN_SERVER, OPTION_DOMAIN_NAME};
+use writer::FrameBuilder;
+use pool::AddressPool;
+use probe::{ProbingPool, IcmpProbe};
+use lease::LeaseStore;
+use pktinfo::{self, PacketInfo};
+
+/*
+ * Lease time sent in offers and acks, in seconds
+ */
+pub const DEFAULT_LEASE_TIME: u32 = 86400;
+
+/*
+ * How long an offered address is held for the client to request it
+ */
+pub const DEFAULT_OFFER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/*
+ * Size of the receive buffer, enough for a full Ethernet frame
+ */
+pub const DEFAULT_RECV_BUFFER_SIZE: usize = 1500;
+
+/*
+ * How often a running Server checks whether it was asked to stop
+ */
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/*
+ * Server settings
+ */
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub bind_addr: SocketAddr,                      // Address to listen on
+    pub server_ip: Ipv4Addr,                        // Server identifier, the bound address if unspecified
+    pub lease_time: u32,                            // Lease time offered, in seconds
+    pub offer_timeout: Duration,                    // Offered addresses go back to the pool if not requested in time
+    pub subnet_mask: option::Option<Ipv4Addr>,      // Offered subnet mask (option 1)
+    pub routers: Vec<Ipv4Addr>,                     // Offered routers (option 3)
+    pub dns_servers: Vec<Ipv4Addr>,                 // Offered DNS servers (option 6)
+    pub domain_name: option::Option<String>,        // Offered domain name (option 15)
+    pub recv_buffer_size: usize,                    // Frames filling the whole buffer are dropped as truncated
+    pub probe_timeout: option::Option<Duration>,    // Ping new addresses before offering them, waiting this long for a reply (blocking server only)
+    pub allowed_macs: option::Option<Vec<Vec<u8>>>, // Only clients with these hardware addresses are answered, all if None
+    pub interface: option::Option<String>,          // Network interface to bind to (SO_BINDTODEVICE, Linux only)
+    pub ttl: option::Option<u32>                    // IP time to live of the packets sent, the system default if None
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            bind_addr: SocketAddr::from((Ipv4Addr::new(0, 0, 0, 0), 67)),
+            server_ip: Ipv4Addr::new(0, 0, 0, 0),
+            lease_time: DEFAULT_LEASE_TIME,
+            offer_timeout: DEFAULT_OFFER_TIMEOUT,
+            subnet_mask: None,
+            routers: Vec::new(),
+            dns_servers: Vec::new(),
+            domain_name: None,
+            recv_buffer_size: DEFAULT_RECV_BUFFER_SIZE,
+            probe_timeout: None,
+            allowed_macs: None,
+            interface: None,
+            ttl: None
+        }
+    }
+}
+
+/*
+ * Chained construction of a ServerConfig, starting from the defaults
+ */
+#[derive(Default)]
+pub struct ServerConfigBuilder {
+    config: ServerConfig
+}
+
+impl ServerConfigBuilder {
+    pub fn new() -> ServerConfigBuilder {
+        ServerConfigBuilder::default()
+    }
+
+    pub fn bind_addr(mut self, addr: SocketAddr) -> ServerConfigBuilder {
+        self.config.bind_addr = addr;
+        self
+    }
+
+    pub fn server_ip(mut self, ip: Ipv4Addr) -> ServerConfigBuilder {
+        self.config.server_ip = ip;
+        self
+    }
+
+    pub fn lease_time(mut self, secs: u32) -> ServerConfigBuilder {
+        self.config.lease_time = secs;
+        self
+    }
+
+    pub fn offer_timeout(mut self, timeout: Duration) -> ServerConfigBuilder {
+        self.config.offer_timeout = timeout;
+        self
+    }
+
+    pub fn subnet_mask(mut self, mask: Ipv4Addr) -> ServerConfigBuilder {
+        self.config.subnet_mask = Some(mask);
+        self
+    }
+
+    /*
+     * Add a router, in order of preference
+     */
+    pub fn router(mut self, ip: Ipv4Addr) -> ServerConfigBuilder {
+        self.config.routers.push(ip);
+        self
+    }
+
+    /*
+     * Add a DNS server, in order of preference
+     */
+    pub fn dns_server(mut self, ip: Ipv4Addr) -> ServerConfigBuilder {
+        self.config.dns_servers.push(ip);
+        self
+    }
+
+    pub fn domain_name(mut self, name: &str) -> ServerConfigBuilder {
+        self.config.domain_name = Some(name.to_string());
+        self
+    }
+
+    pub fn recv_buffer_size(mut self, size: usize) -> ServerConfigBuilder {
+        self.config.recv_buffer_size = size;
+        self
+    }
+
+    /*
+     * Enable conflict detection, see probe::IcmpProbe
+     */
+    pub fn probe_timeout(mut self, timeout: Duration) -> ServerConfigBuilder {
+        self.config.probe_timeout = Some(timeout);
+        self
+    }
+
+    /*
+     * Answer a client hardware address, the first call restricts the server
+     * to the allowed addresses
+     */
+    pub fn allow_mac(mut self, mac: &[u8]) -> ServerConfigBuilder {
+        self.config.allowed_macs.get_or_insert_with(Vec::new).push(mac.to_vec());
+        self
+    }
+
+    /*
+     * Only send and receive on a network interface, see bind_device
+     */
+    pub fn interface(mut self, name: &str) -> ServerConfigBuilder {
+        self.config.interface = Some(name.to_string());
+        self
+    }
+
+    /*
+     * Set the IP time to live of replies (IP_TTL)
+     */
+    pub fn ttl(mut self, ttl: u32) -> ServerConfigBuilder {
+        self.config.ttl = Some(ttl);
+        self
+    }
+
+    pub fn build(self) -> ServerConfig {
+        self.config
+    }
+}
+
+impl FrameBuilder {
+    /*
+     * Start building the reply to a client request: an OFFER to a DISCOVER,
+     * an ACK otherwise, with the server identifier, the lease time unless
+     * answering an INFORM, and the configured options the client asked for
+     * in its parameter request list (option 55). Clients sending no list get
+     * every configured option. The reply is kept within the maximum message
+     * size the client advertised (option 57), and a subnet selection option
+     * (118) is sent back unchanged as RFC 3011 requires.
+     */
+    pub fn respond_to(request: &Frame, config: &ServerConfig) -> FrameBuilder {
+        let reply_type = match request.message_type() {
+            Some(MessageType::Discover) => MessageType::Offer,
+            _ => MessageType::Ack
+        };
+
+        let mut builder = FrameBuilder::reply_to(request)
+            .message_type(reply_type)
+            .server_identifier(config.server_ip);
+
+        if request.message_type() != Some(MessageType::Inform) {
+            builder = builder.lease_time(config.lease_time);
+        }
+
+        if let Some(opt) = request.option(OPTION_SUBNET_SELECTION) {
+            builder = builder.option(opt.clone());
+        }
+
+        let requested = request.requested_parameters();
+
+        for (tag, opt) in config_options(config) {
+            if request.option(OPTION_PARAMETER_LIST).is_some() && !requested.contains(&tag) {
+                continue;
+            }
+
+            match opt {
+                Ok(opt) => builder = builder.option(opt),
+                Err(e) => builder.fail(e)
+            }
+        }
+
+        builder
+    }
+}
+
+/*
+ * The network options a server has values for, with their tags
+ */
+fn config_options(config: &ServerConfig) -> Vec<(u8, Result<Option>)> {
+    let mut opts = Vec::new();
+
+    if let Some(mask) = config.subnet_mask {
+        opts.push((OPTION_SUBNET_MASK, Ok(Option::ipv4(OPTION_SUBNET_MASK, mask))));
+    }
+
+    if !config.routers.is_empty() {
+        opts.push((OPTION_ROUTER, Option::ipv4_list(OPTION_ROUTER, &config.routers)));
+    }
+
+    if !config.dns_servers.is_empty() {
+        opts.push((OPTION_DOMAIN_SERVER, Option::ipv4_list(OPTION_DOMAIN_SERVER, &config.dns_servers)));
+    }
+
+    if let Some(ref name) = config.domain_name {
+        let mut opt = Option::new(OPTION_DOMAIN_NAME);
+        opts.push((OPTION_DOMAIN_NAME, opt.set_data_str(name).map(|_| opt)));
+    }
+
+    opts
+}
+
+/*
+ * A server answering DHCP requests on its own thread until stopped
+ */
+pub struct Server {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Result<()>>
+}
+
+impl Server {
+    /*
+     * Bind to the configured address and start answering requests
+     */
+    pub fn start(config: &ServerConfig, pool: impl AddressPool + Send + 'static) -> Result<Server> {
+        let socket = bind_config(config)?;
+        socket.set_read_timeout(Some(STOP_POLL_INTERVAL))?;
+
+        let addr = socket.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let (config, with_pktinfo) = prepare(&socket, config)?;
+
+        // The socket is moved to the thread and closed when the loop exits
+        let thread = thread::spawn(move || run(&socket, &config, with_pktinfo, pool, &flag));
+
+        Ok(Server {
+            addr,
+            stop,
+            thread
+        })
+    }
+
+    /*
+     * Address the server is bound to
+     */
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /*
+     * Ask the server to stop and wait for it, returning the loop's result
+     */
+    pub fn stop(self) -> Result<()> {
+        self.stop.store(true, Ordering::SeqCst);
+
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(_) => Err(Error::new("Server thread panicked"))
+        }
+    }
+}
+
+/*
+ * Bind to the configured address and answer DHCP requests forever, handing
+ * out addresses from a pool
+ */
+pub fn listen(config: &ServerConfig, pool: impl AddressPool) -> Result<()> {
+    let socket = bind_config(config)?;
+    serve(&socket, config, pool)
+}
+
+/*
+ * Bind a UDP socket suitable for a DHCP server: allowed to send broadcasts
+ * and to rebind right after a restart (SO_BROADCAST, SO_REUSEADDR)
+ */
+pub fn bind(addr: impl ToSocketAddrs) -> Result<UdpSocket> {
+    bind_with(addr, None)
+}
+
+/*
+ * Bind a server socket, see bind, that only sends and receives on a network
+ * interface (SO_BINDTODEVICE). On a multi-homed host this is what keeps
+ * broadcasts from the other links away. Linux only; kernels before 5.7
+ * require CAP_NET_RAW.
+ */
+pub fn bind_device(addr: impl ToSocketAddrs, interface: &str) -> Result<UdpSocket> {
+    bind_with(addr, Some(interface))
+}
+
+/*
+ * Bind the socket described by a configuration: address, interface and TTL
+ */
+pub(crate) fn bind_config(config: &ServerConfig) -> Result<UdpSocket> {
+    let socket = bind_with(config.bind_addr, config.interface.as_deref())?;
+
+    if let Some(ttl) = config.ttl {
+        socket.set_ttl(ttl)?;
+    }
+
+    Ok(socket)
+}
+
+/*
+ * Bind a server socket, restricted to an interface if one is given
+ */
+fn bind_with(addr: impl ToSocketAddrs, interface: option::Option<&str>) -> Result<UdpSocket> {
+    let addr = match addr.to_socket_addrs()?.next() {
+        Some(addr) => addr,
+        None => return Err(Error::with_kind(ErrorKind::InvalidValue, "No address to bind"))
+    };
+
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_broadcast(true)?;
+
+    if let Some(name) = interface {
+        bind_to_device(&socket, name)?;
+    }
+
+    socket.bind(&addr.into())?;
+
+    Ok(socket.into())
+}
+
+/*
+ * Set SO_BINDTODEVICE, failing if the interface does not exist or the
+ * process lacks the privilege
+ */
+#[cfg(target_os = "linux")]
+fn bind_to_device(socket: &Socket, name: &str) -> Result<()> {
+    socket.bind_device(Some(name.as_bytes())).map_err(|e| {
+        let mut err = Error::from(e);
+        err.msg = format!("Failed to bind to interface {} (SO_BINDTODEVICE may need CAP_NET_RAW): {}", name, err.msg);
+        err
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bind_to_device(_socket: &Socket, name: &str) -> Result<()> {
+    Err(Error::with_kind(ErrorKind::InvalidValue, format!("Cannot bind to interface {}: only supported on Linux", name)))
+}
+
+/*
+ * Answer DHCP requests received on an already bound socket
+ */
+pub fn serve(socket: &UdpSocket, config: &ServerConfig, pool: impl AddressPool) -> Result<()> {
+    let (config, with_pktinfo) = prepare(socket, config)?;
+    run(socket, &config, with_pktinfo, pool, &AtomicBool::new(false))
+}
+
+/*
+ * Get ready to answer on a bound socket: ask for the ingress interface of
+ * requests if possible and settle the server identifier
+ */
+fn prepare(socket: &UdpSocket, config: &ServerConfig) -> Result<(ServerConfig, bool)> {
+    // Knowing the ingress interface only matters for IPv4 sockets
+    let with_pktinfo = socket.local_addr()?.is_ipv4() && pktinfo::enable(socket).is_ok();
+    let config = local_config(config, socket.local_addr()?, with_pktinfo)?;
+
+    Ok((config, with_pktinfo))
+}
+
+/*
+ * Answer DHCP requests until stop is set, probing addresses first if
+ * configured to
+ */
+fn run(socket: &UdpSocket, config: &ServerConfig, with_pktinfo: bool, pool: impl AddressPool, stop: &AtomicBool) -> Result<()> {
+    match config.probe_timeout {
+        Some(timeout) => run_with(socket, config, with_pktinfo, ProbingPool::new(pool, IcmpProbe::new(timeout)), stop),
+        None => run_with(socket, config, with_pktinfo, pool, stop)
+    }
+}
+
+/*
+ * The receive loop of run, stop is checked whenever the socket read times
+ * out
+ */
+fn run_with(socket: &UdpSocket, config: &ServerConfig, with_pktinfo: bool, mut pool: impl AddressPool, stop: &AtomicBool) -> Result<()> {
+    let mut leases = LeaseStore::new();
+    let mut buf = vec![0; config.recv_buffer_size];
+
+    while !stop.load(Ordering::SeqCst) {
+        let (len, src, info) = match receive(socket, &mut buf, with_pktinfo)? {
+            Some(received) => received,
+            None => continue
+        };
+
+        // The link the request came in on, when known
+        let local_ip = match info {
+            Some(ref info) => info.local_ip,
+            None => config.server_ip
+        };
+
+        if let Some((bytes, dst)) = respond(&buf[..len], src, config, local_ip, &mut pool, &mut leases)? {
+            let sent = match info {
+                Some(ref info) => pktinfo::send(socket, &bytes, dst, info),
+                None => socket.send_to(&bytes, dst)
+            };
+
+            if let Err(e) = sent {
+                error!("Failed to send response to {}: {}", dst, e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Use the bound address as server identifier when none is configured. A
+ * server bound to all interfaces can only take the address requests arrive
+ * on if it knows it (with_pktinfo), it would identify as 0.0.0.0 otherwise.
+ */
+pub(crate) fn local_config(config: &ServerConfig, local: SocketAddr, with_pktinfo: bool) -> Result<ServerConfig> {
+    let mut config = config.clone();
+
+    if config.server_ip.is_unspecified() {
+        if let SocketAddr::V4(addr) = local {
+            config.server_ip = *addr.ip();
+        }
+    }
+
+    if config.server_ip.is_unspecified() && !with_pktinfo {
+        return Err(Error::with_kind(ErrorKind::InvalidValue, format!("No server identifier: set server_ip or bind to an address rather than {}", local)));
+    }
+
+    Ok(config)
+}
+
+/*
+ * Parse a datagram received on local_ip and build the bytes to send back,
+ * with their destination (see handle_as). Frames that cannot be parsed or
+ * answered are logged and dropped.
+ */
+pub(crate) fn respond(data: &[u8], src: SocketAddr, config: &ServerConfig, local_ip: Ipv4Addr, pool: &mut impl AddressPool, leases: &mut LeaseStore) -> Result<option::Option<(Vec<u8>, SocketAddr)>> {
+    let frame = match Frame::parse(data) {
+        Ok(frame) => frame,
+        Err(e) => {
+            error!("Failed to parse frame from {}: {}", src, e);
+            return Ok(None);
+        }
+    };
+
+    debug!("Received {:?} from {} ({})", frame.message_type(), frame.client_mac_string(), src);
+
+    let reply = match handle_as(&frame, config, local_ip, pool, leases) {
+        Ok(Some(reply)) => reply,
+        Ok(None) => {
+            debug!("Not answering {}", frame.client_mac_string());
+            return Ok(None);
+        },
+        Err(e) => {
+            error!("Failed to build response to {}: {}", src, e);
+            return Ok(None);
+        }
+    };
+
+    // A NAK is broadcast unless relayed, the client may not hold its address
+    let dst = if reply.message_type() == Some(MessageType::Nak) && frame.relay_ip().is_unspecified() {
+        SocketAddr::from((Ipv4Addr::new(255, 255, 255, 255), 68))
+    } else {
+        destination(&frame, src)
+    };
+
+    match reply.message_type() {
+        Some(MessageType::Nak) => info!("Sending NAK to {} at {}", frame.client_mac_string(), dst),
+        t => debug!("Sending {:?} of {} to {} at {}", t, reply.your_ip(), frame.client_mac_string(), dst)
+    }
+
+    Ok(Some((reply.to_bytes_padded(BOOTP_MIN_LEN)?, dst)))
+}
+
+/*
+ * Receive a datagram, with where it was received if with_pktinfo is set.
+ * None if the read timed out or if it filled the whole buffer and may have
+ * been truncated.
+ */
+pub(crate) fn receive(socket: &UdpSocket, buf: &mut [u8], with_pktinfo: bool) -> Result<option::Option<(usize, SocketAddr, option::Option<PacketInfo>)>> {
+    let received = if with_pktinfo {
+        pktinfo::recv(socket, buf)
+    } else {
+        socket.recv_from(buf).map(|(len, src)| (len, src, None))
+    };
+
+    let (len, src, info) = match received {
+        Ok(received) => received,
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => return Ok(None),
+        Err(e) => return Err(e.into())
+    };
+
+    if len == buf.len() {
+        warn!("Dropping frame from {}: possibly truncated to {} bytes", src, len);
+        return Ok(None);
+    }
+
+    Ok(Some((len, src, info)))
+}
+
+/*
+ * Build the response to a client frame, if any. Expired leases are
+ * reclaimed before allocating, offered addresses are held for the offer
+ * timeout and acknowledged addresses are leased.
+ */
+pub fn handle(frame: &Frame, config: &ServerConfig, pool: &mut impl AddressPool, leases: &mut LeaseStore) -> Result<option::Option<Frame>> {
+    handle_as(frame, config, config.server_ip, pool, leases)
+}
+
+/*
+ * Build the response to a client frame as handle does, for a frame received
+ * on local_ip. Directly connected clients get addresses on its network, and
+ * it is the server identifier when none is configured.
+ */
+pub(crate) fn handle_as(frame: &Frame, config: &ServerConfig, local_ip: Ipv4Addr, pool: &mut impl AddressPool, leases: &mut LeaseStore) -> Result<option::Option<Frame>> {
+    if frame.opcode() != Opcode::Request {
+        return Ok(None);
+    }
+
+    // Bound to all interfaces, answer as the one the request came in on
+    let server_ip = if config.server_ip.is_unspecified() {
+        local_ip
+    } else {
+        config.server_ip
+    };
+
+    if !is_allowed(frame, config) {
+        return Ok(None);
+    }
+
+    let key = frame.client_key();
+
+    // A REQUEST, DECLINE or RELEASE meant for another server is not for us
+    if let Some(id) = frame.server_identifier() {
+        if frame.message_type() != Some(MessageType::Discover) && id != server_ip {
+            return Ok(None);
+        }
+    }
+
+    let reply_type = match frame.message_type() {
+        Some(MessageType::Discover) => MessageType::Offer,
+        Some(MessageType::Request) => MessageType::Ack,
+        Some(MessageType::Decline) => {
+            // The address is in use by someone else, never hand it out again
+            leases.remove(&key);
+            pool.decline(&key);
+            return Ok(None);
+        },
+        Some(MessageType::Release) => {
+            leases.remove(&key);
+            pool.release(&key);
+            return Ok(None);
+        },
+        // The client already has an address and only wants the
+        // configuration, so there is no yiaddr (RFC 2131 section 3.4)
+        Some(MessageType::Inform) => {
+            let mut reply = FrameBuilder::respond_to(frame, config)
+                .server_identifier(server_ip)
+                .build()?;
+            reply.ciaddr = frame.ciaddr.clone();
+
+            return Ok(Some(reply));
+        },
+        _ => return Ok(None)
+    };
+
+    leases.expire(pool, Instant::now());
+
+    // The subnet asked for, else the relay agent's network, else the one
+    // the request came in on
+    let link = match frame.subnet_selection() {
+        Some(subnet) => subnet,
+        None if !frame.relay_ip().is_unspecified() => frame.relay_ip(),
+        None => local_ip
+    };
+
+    let ip = match pool.allocate_on(&key, link) {
+        Some(ip) => ip,
+        None if reply_type == MessageType::Ack => return Ok(Some(nak(frame, server_ip))),
+        None => return Ok(None)
+    };
+
+    if reply_type == MessageType::Ack {
+        // The client asked for an address we cannot grant it
+        let requested = match frame.dhcp_state() {
+            Some(RequestState::Selecting) | Some(RequestState::InitReboot) => frame.requested_ip(),
+            Some(RequestState::Renewing) | Some(RequestState::Rebinding) => Some(frame.client_ip()),
+            None => None
+        };
+
+        if requested.is_some_and(|requested| requested != ip) {
+            // Nothing would ever free a binding made for this request alone
+            if leases.get(&key).is_none() {
+                pool.release(&key);
+            }
+
+            return Ok(Some(nak(frame, server_ip)));
+        }
+
+        leases.renew(&key, ip, Duration::from_secs(config.lease_time as u64));
+    } else {
+        // Back to the pool if the client never requests it (RFC 2131 section 4.3.1)
+        leases.reserve(&key, ip, config.offer_timeout);
+    }
+
+    let reply = FrameBuilder::respond_to(frame, config)
+        .server_identifier(server_ip)
+        .your_ip(ip)
+        .server_ip(server_ip)
+        .build()?;
+
+    Ok(Some(reply))
+}
+
+/*
+ * Whether the client is on the allow-list, if any. Every entry is compared
+ * so the time taken does not tell which one matched.
+ */
+fn is_allowed(frame: &Frame, config: &ServerConfig) -> bool {
+    let allowed = match config.allowed_macs {
+        Some(ref allowed) => allowed,
+        None => return true
+    };
+
+    let mac = frame.hardware_address();
+    allowed.iter().fold(false, |found, m| secure_eq(m, mac) | found)
+}
+
+/*
+ * Compare two byte strings in constant time for a given length, for client
+ * identifiers and hardware addresses
+ */
+pub fn secure_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/*
+ * Build the DHCPNAK refusing a REQUEST. It carries the broadcast flag so
+ * relay agents broadcast it too (RFC 2131 section 4.1).
+ */
+fn nak(frame: &Frame, server_ip: Ipv4Addr) -> Frame {
+    let mut reply = Frame::new_reply(frame);
+    reply.add_option(Option::u8(OPTION_DHCP_MSG_TYPE, MessageType::Nak.into()));
+
+    reply.set_broadcast(true);
+    reply.set_server_identifier(server_ip);
+
+    reply
+}
+
+/*
+ * Where to send the response to a client frame (RFC 2131 section 4.1): to
+ * the relay agent if the frame was relayed, to ciaddr for a renewing client,
+ * broadcast if the client asked for it, back to the sender otherwise
+ */
+fn destination(frame: &Frame, src: SocketAddr) -> SocketAddr {
+    let relay = frame.relay_ip();
+
+    if !relay.is_unspecified() {
+        return SocketAddr::from((relay, 67));
+    }
+
+    let client = frame.client_ip();
+
+    if !client.is_unspecified() {
+        return SocketAddr::from((client, 68));
+    }
+
+    if frame.broadcast() {
+        return SocketAddr::from((Ipv4Addr::new(255, 255, 255, 255), 68));
+    }
+
+    src
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{UdpSocket, Ipv4Addr};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use log::{self, Log, Level, LevelFilter, Metadata, Record};
+
+    use common::{Option, Frame, MessageType, Opcode};
+    use writer::FrameBuilder;
+    use pool::{RangePool, SubnetPools};
+    use codes::OPTION_DOMAIN_NAME;
+    use lease::LeaseStore;
+    use super::{ServerConfig, ServerConfigBuilder};
+
+    fn config(server_ip: Ipv4Addr) -> ServerConfig {
+        ServerConfigBuilder::new().server_ip(server_ip).build()
+    }
+
+    #[test]
+    fn test_config_builder() {
+        let config = ServerConfigBuilder::new()
+            .bind_addr("10.0.0.1:67".parse().unwrap())
+            .server_ip(Ipv4Addr::new(10, 0, 0, 1))
+            .lease_time(3600)
+            .subnet_mask(Ipv4Addr::new(255, 255, 255, 0))
+            .router(Ipv4Addr::new(10, 0, 0, 254))
+            .dns_server(Ipv4Addr::new(10, 0, 0, 2))
+            .dns_server(Ipv4Addr::new(10, 0, 0, 3))
+            .build();
+
+        assert_eq!(config.bind_addr, "10.0.0.1:67".parse().unwrap());
+        assert_eq!(config.server_ip, Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(config.lease_time, 3600);
+        assert_eq!(config.subnet_mask, Some(Ipv4Addr::new(255, 255, 255, 0)));
+        assert_eq!(config.routers, [Ipv4Addr::new(10, 0, 0, 254)]);
+        assert_eq!(config.dns_servers, [Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(10, 0, 0, 3)]);
+        assert_eq!(config.recv_buffer_size, super::DEFAULT_RECV_BUFFER_SIZE);
+
+        let default = ServerConfig::default();
+
+        assert_eq!(default.lease_time, super::DEFAULT_LEASE_TIME);
+        assert!(default.server_ip.is_unspecified());
+        assert!(default.routers.is_empty());
+    }
+
+    #[test]
+    fn test_discover_offer() {
+        let config = ServerConfigBuilder::new()
+            .bind_addr("127.0.0.1:0".parse().unwrap())
+            .build();
+
+        let server = super::Server::start(&config, RangePool::new(Ipv4Addr::new(127, 0, 0, 10), Ipv4Addr::new(127, 0, 0, 20))).unwrap();
+        let server_addr = server.local_addr();
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let discover = FrameBuilder::new(Opcode::Request, 0x6e86444c)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+
+        client.send_to(&discover.to_bytes().unwrap(), server_addr).unwrap();
+
+        let mut buf = [0; 1024];
+        let (len, _) = client.recv_from(&mut buf).unwrap();
+        let offer = Frame::parse(&buf[..len]).unwrap();
+
+        assert_eq!(offer.op, 2);
+        assert_eq!(offer.xid, 0x6e86444c);
+        assert_eq!(offer.message_type(), Some(MessageType::Offer));
+        assert_eq!(offer.your_ip(), Ipv4Addr::new(127, 0, 0, 10));
+        assert_eq!(offer.client_mac_string().as_str(), "52:54:01:12:34:56");
+        assert_eq!(offer.option(54).unwrap().data, [127, 0, 0, 1]);
+
+        server.stop().unwrap();
+    }
+
+    #[test]
+    fn test_local_config() {
+        let default = ServerConfig::default();
+
+        let config = super::local_config(&default, "10.0.0.1:67".parse().unwrap(), false).unwrap();
+        assert_eq!(config.server_ip, Ipv4Addr::new(10, 0, 0, 1));
+
+        // Bound to all interfaces, the ingress address must be known
+        let e = super::local_config(&default, "0.0.0.0:67".parse().unwrap(), false).err().unwrap();
+        assert_eq!(e.kind(), ::common::ErrorKind::InvalidValue);
+        assert!(super::local_config(&default, "0.0.0.0:67".parse().unwrap(), true).is_ok());
+        assert!(super::local_config(&config, "0.0.0.0:67".parse().unwrap(), false).is_ok());
+    }
+
+    #[test]
+    fn test_bind_broadcast() {
+        let socket = super::bind("127.0.0.1:0").unwrap();
+
+        assert!(socket.broadcast().unwrap());
+        assert!(super::bind("not an address").is_err());
+    }
+
+    #[test]
+    fn test_bind_ttl() {
+        let config = ServerConfigBuilder::new()
+            .bind_addr("127.0.0.1:0".parse().unwrap())
+            .ttl(16)
+            .build();
+
+        assert_eq!(super::bind_config(&config).unwrap().ttl().unwrap(), 16);
+        assert_eq!(ServerConfig::default().ttl, None);
+    }
+
+    #[test]
+    fn test_bind_bogus_interface() {
+        assert!(super::bind_device("127.0.0.1:0", "nosuchif0").is_err());
+
+        let config = ServerConfigBuilder::new()
+            .bind_addr("127.0.0.1:0".parse().unwrap())
+            .interface("nosuchif0")
+            .build();
+
+        let e = super::Server::start(&config, RangePool::new(Ipv4Addr::new(127, 0, 0, 10), Ipv4Addr::new(127, 0, 0, 20))).err().unwrap();
+        assert!(e.msg.contains("nosuchif0"));
+    }
+
+    #[test]
+    fn test_server_stop() {
+        let config = ServerConfigBuilder::new()
+            .bind_addr("127.0.0.1:0".parse().unwrap())
+            .build();
+
+        let server = super::Server::start(&config, RangePool::new(Ipv4Addr::new(127, 0, 0, 10), Ipv4Addr::new(127, 0, 0, 20))).unwrap();
+        let server_addr = server.local_addr();
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let discover = FrameBuilder::new(Opcode::Request, 42)
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+
+        client.send_to(&discover.to_bytes().unwrap(), server_addr).unwrap();
+
+        let mut buf = [0; 1024];
+        let (len, _) = client.recv_from(&mut buf).unwrap();
+        assert_eq!(Frame::parse(&buf[..len]).unwrap().message_type(), Some(MessageType::Offer));
+
+        server.stop().unwrap();
+
+        // Nothing answers any more
+        client.set_read_timeout(Some(Duration::from_millis(300))).unwrap();
+        client.send_to(&discover.to_bytes().unwrap(), server_addr).unwrap();
+        assert!(client.recv_from(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_receive_large_frame() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let data: Vec<u8> = (0..1400).map(|i| i as u8).collect();
+        let mut buf = vec![0; super::ServerConfig::default().recv_buffer_size];
+
+        client.send_to(&data, server.local_addr().unwrap()).unwrap();
+        let (len, _, _) = super::receive(&server, &mut buf, false).unwrap().unwrap();
+
+        assert_eq!(&buf[..len], data.as_slice());
+
+        // Too large for the buffer
+        client.send_to(&data, server.local_addr().unwrap()).unwrap();
+        assert!(super::receive(&server, &mut buf[..1024], false).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_request_ack() {
+        let request = FrameBuilder::new(Opcode::Request, 42)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(MessageType::Request)
+            .build()
+            .unwrap();
+
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 9));
+        let mut leases = LeaseStore::new();
+        let config = ServerConfigBuilder::new()
+            .server_ip(Ipv4Addr::new(10, 0, 0, 1))
+            .lease_time(3600)
+            .subnet_mask(Ipv4Addr::new(255, 255, 255, 0))
+            .router(Ipv4Addr::new(10, 0, 0, 1))
+            .dns_server(Ipv4Addr::new(10, 0, 0, 2))
+            .build();
+
+        let ack = super::handle(&request, &config, &mut pool, &mut leases).unwrap().unwrap();
+
+        assert_eq!(ack.xid, 42);
+        assert_eq!(ack.message_type(), Some(MessageType::Ack));
+        assert_eq!(ack.your_ip(), Ipv4Addr::new(10, 0, 0, 5));
+        assert_eq!(ack.lease_time(), Some(3600));
+        assert_eq!(ack.subnet_mask(), Some(Ipv4Addr::new(255, 255, 255, 0)));
+        assert_eq!(ack.routers(), [Ipv4Addr::new(10, 0, 0, 1)]);
+        assert_eq!(ack.dns_servers(), [Ipv4Addr::new(10, 0, 0, 2)]);
+        assert_eq!(leases.get(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56]).unwrap().ip, Ipv4Addr::new(10, 0, 0, 5));
+    }
+
+    #[test]
+    fn test_request_other_server_ignored() {
+        let mut request = FrameBuilder::new(Opcode::Request, 42)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(MessageType::Request)
+            .build()
+            .unwrap();
+
+        request.set_server_identifier(Ipv4Addr::new(10, 0, 0, 2));
+
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 9));
+        let reply = super::handle(&request, &config(Ipv4Addr::new(10, 0, 0, 1)), &mut pool, &mut LeaseStore::new()).unwrap();
+
+        assert!(reply.is_none());
+    }
+
+    #[test]
+    fn test_client_identifier_leases() {
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 9));
+        let mut leases = LeaseStore::new();
+        let mut ips = Vec::new();
+
+        // Same hardware address, different client identifiers
+        for id in &[[0x00, 0x01], [0x00, 0x02]] {
+            let mut client_id = Option::new(61);
+            client_id.set_data(id.to_vec()).unwrap();
+
+            let request = FrameBuilder::new(Opcode::Request, 42)
+                .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+                .message_type(MessageType::Request)
+                .option(client_id)
+                .build()
+                .unwrap();
+
+            let ack = super::handle(&request, &config(Ipv4Addr::new(10, 0, 0, 1)), &mut pool, &mut leases).unwrap().unwrap();
+            ips.push(ack.your_ip());
+        }
+
+        assert_ne!(ips[0], ips[1]);
+        assert_eq!(leases.len(), 2);
+        assert_eq!(leases.get(&[0x00, 0x02]).unwrap().ip, ips[1]);
+    }
+
+    #[test]
+    fn test_relayed_destination() {
+        let mut data = vec![
+            0x01, 0x01, 0x06, 0x01, 0x6e, 0x86, 0x44, 0x4c,
+            0x00, 0x00, 0x80, 0x00
+        ];
+
+        data.extend(vec![0; 12]);
+        data.extend(vec![192, 168, 1, 1]); // giaddr
+        data.extend(vec![0; 16 + 64 + 128]);
+        data.extend(vec![0x63, 0x82, 0x53, 0x63, 0x35, 0x01, 0x01, 0xff]);
+
+        let discover = Frame::parse(&data).unwrap();
+        let src = "192.168.1.1:67".parse().unwrap();
+
+        assert_eq!(super::destination(&discover, src), "192.168.1.1:67".parse().unwrap());
+
+        let mut pool = RangePool::new(Ipv4Addr::new(192, 168, 1, 10), Ipv4Addr::new(192, 168, 1, 20));
+        let offer = super::handle(&discover, &config(Ipv4Addr::new(10, 0, 0, 1)), &mut pool, &mut LeaseStore::new()).unwrap().unwrap();
+
+        assert_eq!(offer.relay_ip(), Ipv4Addr::new(192, 168, 1, 1));
+
+        // Not relayed, broadcast flag set
+        data[24..28].copy_from_slice(&[0, 0, 0, 0]);
+        let discover = Frame::parse(&data).unwrap();
+
+        assert_eq!(super::destination(&discover, src), "255.255.255.255:68".parse().unwrap());
+
+        // Renewing client
+        let mut request = discover.clone();
+        request.set_client_ip(Ipv4Addr::new(192, 168, 1, 12));
+
+        assert_eq!(super::destination(&request, src), "192.168.1.12:68".parse().unwrap());
+
+        request.set_client_ip(Ipv4Addr::new(0, 0, 0, 0));
+        request.set_broadcast(false);
+
+        assert_eq!(super::destination(&request, src), src);
+    }
+
+    #[test]
+    fn test_request_unavailable_nak() {
+        let mut request = FrameBuilder::new(Opcode::Request, 42)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(MessageType::Request)
+            .build()
+            .unwrap();
+
+        // Outside of the pool
+        request.set_requested_ip(Ipv4Addr::new(192, 168, 1, 50));
+
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 9));
+        let mut leases = LeaseStore::new();
+        let nak = super::handle(&request, &config(Ipv4Addr::new(10, 0, 0, 1)), &mut pool, &mut leases).unwrap().unwrap();
+
+        assert_eq!(nak.message_type(), Some(MessageType::Nak));
+        assert_eq!(nak.server_identifier(), Some(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(nak.your_ip(), Ipv4Addr::new(0, 0, 0, 0));
+        assert!(nak.lease_time().is_none());
+        assert!(nak.broadcast());
+        assert!(leases.is_empty());
+
+        let (_, dst) = super::respond(&request.to_bytes().unwrap(), "10.0.0.7:68".parse().unwrap(), &config(Ipv4Addr::new(10, 0, 0, 1)), Ipv4Addr::new(10, 0, 0, 1), &mut pool, &mut leases).unwrap().unwrap();
+        assert_eq!(dst, "255.255.255.255:68".parse().unwrap());
+
+        // The refused client holds nothing, the address goes to the next one
+        assert!(pool.binding(&request.client_key()).is_none());
+        assert_eq!(pool.available(), 5);
+
+        let offer = exchange(MessageType::Discover, &[0x52, 0x54, 0x01, 0x65, 0x43, 0x21], &mut pool, &mut leases).unwrap();
+        assert_eq!(offer.your_ip(), Ipv4Addr::new(10, 0, 0, 5));
+
+        // Nothing left to allocate
+        let mut empty = RangePool::new(Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 4));
+        request.options.retain(|opt| opt.tag != 50);
+
+        let nak = super::handle(&request, &config(Ipv4Addr::new(10, 0, 0, 1)), &mut empty, &mut leases).unwrap().unwrap();
+        assert_eq!(nak.message_type(), Some(MessageType::Nak));
+    }
+
+    #[test]
+    fn test_nak_hardware_type() {
+        let mut request = FrameBuilder::new(Opcode::Request, 42)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56, 0x78, 0x9a])
+            .message_type(MessageType::Request)
+            .build()
+            .unwrap();
+
+        // IEEE 802 networks, INIT-REBOOT for an address outside of the pool
+        request.htype = 6;
+        request.set_requested_ip(Ipv4Addr::new(192, 168, 1, 50));
+
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 9));
+        let mut leases = LeaseStore::new();
+        let nak = super::handle(&request, &config(Ipv4Addr::new(10, 0, 0, 1)), &mut pool, &mut leases).unwrap().unwrap();
+
+        assert_eq!(nak.message_type(), Some(MessageType::Nak));
+        assert_eq!(nak.htype, 6);
+        assert_eq!(nak.hlen, 8);
+        assert_eq!(nak.hardware_address(), request.hardware_address());
+    }
+
+    /*
+     * Send a message of the given type for a client, returning the reply
+     */
+    fn exchange(msg_type: MessageType, mac: &[u8], pool: &mut RangePool, leases: &mut LeaseStore) -> ::std::option::Option<Frame> {
+        let mut frame = FrameBuilder::new(Opcode::Request, 42)
+            .client_mac(mac)
+            .message_type(msg_type)
+            .build()
+            .unwrap();
+
+        if msg_type != MessageType::Discover {
+            frame.set_server_identifier(Ipv4Addr::new(10, 0, 0, 1));
+        }
+
+        super::handle(&frame, &config(Ipv4Addr::new(10, 0, 0, 1)), pool, leases).unwrap()
+    }
+
+    #[test]
+    fn test_offers_expire() {
+        let config = ServerConfigBuilder::new()
+            .server_ip(Ipv4Addr::new(10, 0, 0, 1))
+            .offer_timeout(Duration::from_millis(20))
+            .build();
+
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 5));
+        let mut leases = LeaseStore::new();
+
+        let discover = |mac: u8| FrameBuilder::new(Opcode::Request, 42)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, mac])
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+
+        // Clients that never send a REQUEST do not keep the only address
+        for mac in 0..5 {
+            let offer = super::handle(&discover(mac), &config, &mut pool, &mut leases).unwrap().unwrap();
+            assert_eq!(offer.your_ip(), Ipv4Addr::new(10, 0, 0, 5));
+
+            // Held until the offer times out
+            assert!(super::handle(&discover(0xff), &config, &mut pool, &mut leases).unwrap().is_none());
+
+            ::std::thread::sleep(Duration::from_millis(30));
+        }
+    }
+
+    #[test]
+    fn test_decline_release() {
+        let a = [0x52, 0x54, 0x01, 0x12, 0x34, 0x56];
+        let b = [0x52, 0x54, 0x01, 0x12, 0x34, 0x57];
+
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 9));
+        let mut leases = LeaseStore::new();
+
+        let ack = exchange(MessageType::Request, &a, &mut pool, &mut leases).unwrap();
+        assert_eq!(ack.your_ip(), Ipv4Addr::new(10, 0, 0, 5));
+
+        // Declined: quarantined, the client gets another address
+        assert!(exchange(MessageType::Decline, &a, &mut pool, &mut leases).is_none());
+        assert!(leases.is_empty());
+
+        let offer = exchange(MessageType::Discover, &a, &mut pool, &mut leases).unwrap();
+        assert_eq!(offer.your_ip(), Ipv4Addr::new(10, 0, 0, 6));
+
+        let offer = exchange(MessageType::Discover, &b, &mut pool, &mut leases).unwrap();
+        assert_eq!(offer.your_ip(), Ipv4Addr::new(10, 0, 0, 7));
+
+        // Released: free for the next client
+        exchange(MessageType::Request, &a, &mut pool, &mut leases).unwrap();
+        assert!(exchange(MessageType::Release, &a, &mut pool, &mut leases).is_none());
+        assert!(leases.get(&a).is_none());
+
+        let offer = exchange(MessageType::Discover, &[0x52, 0x54, 0x01, 0x12, 0x34, 0x58], &mut pool, &mut leases).unwrap();
+        assert_eq!(offer.your_ip(), Ipv4Addr::new(10, 0, 0, 6));
+    }
+
+    #[test]
+    fn test_inform() {
+        let config = ServerConfigBuilder::new()
+            .server_ip(Ipv4Addr::new(10, 0, 0, 1))
+            .router(Ipv4Addr::new(10, 0, 0, 254))
+            .dns_server(Ipv4Addr::new(10, 0, 0, 2))
+            .domain_name("example.org")
+            .build();
+
+        let mut inform = FrameBuilder::new(Opcode::Request, 42)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(MessageType::Inform)
+            .build()
+            .unwrap();
+        inform.ciaddr = vec![10, 0, 0, 7];
+
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 9));
+        let mut leases = LeaseStore::new();
+
+        let ack = super::handle(&inform, &config, &mut pool, &mut leases).unwrap().unwrap();
+
+        assert_eq!(ack.message_type(), Some(MessageType::Ack));
+        assert_eq!(ack.your_ip(), Ipv4Addr::new(0, 0, 0, 0));
+        assert_eq!(ack.client_ip(), Ipv4Addr::new(10, 0, 0, 7));
+        assert_eq!(ack.routers(), vec![Ipv4Addr::new(10, 0, 0, 254)]);
+        assert_eq!(ack.dns_servers(), vec![Ipv4Addr::new(10, 0, 0, 2)]);
+        assert_eq!(ack.option(OPTION_DOMAIN_NAME).unwrap().data, b"example.org");
+        assert!(ack.lease_time().is_none());
+
+        // Nothing was allocated
+        assert!(leases.is_empty());
+        assert_eq!(pool.binding(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56]), None);
+    }
+
+    #[test]
+    fn test_relayed_subnets() {
+        let mut pools = SubnetPools::new();
+        pools.add_subnet(Ipv4Addr::new(10, 0, 1, 0), Ipv4Addr::new(255, 255, 255, 0), RangePool::new(Ipv4Addr::new(10, 0, 1, 10), Ipv4Addr::new(10, 0, 1, 20)));
+        pools.add_subnet(Ipv4Addr::new(10, 0, 2, 0), Ipv4Addr::new(255, 255, 255, 0), RangePool::new(Ipv4Addr::new(10, 0, 2, 10), Ipv4Addr::new(10, 0, 2, 20)));
+
+        let config = config(Ipv4Addr::new(10, 0, 0, 1));
+        let mut leases = LeaseStore::new();
+
+        let mut request = |relay: Ipv4Addr, mac: &[u8]| {
+            let mut frame = FrameBuilder::new(Opcode::Request, 42)
+                .client_mac(mac)
+                .message_type(MessageType::Request)
+                .build()
+                .unwrap();
+            frame.set_relay_ip(relay);
+
+            super::handle(&frame, &config, &mut pools, &mut leases).unwrap().unwrap()
+        };
+
+        let ack = request(Ipv4Addr::new(10, 0, 1, 1), &[0x52, 0x54, 0x01, 0x12, 0x34, 0x56]);
+        assert_eq!(ack.message_type(), Some(MessageType::Ack));
+        assert_eq!(ack.your_ip(), Ipv4Addr::new(10, 0, 1, 10));
+        assert_eq!(ack.relay_ip(), Ipv4Addr::new(10, 0, 1, 1));
+
+        let ack = request(Ipv4Addr::new(10, 0, 2, 1), &[0x52, 0x54, 0x01, 0x12, 0x34, 0x57]);
+        assert_eq!(ack.message_type(), Some(MessageType::Ack));
+        assert_eq!(ack.your_ip(), Ipv4Addr::new(10, 0, 2, 10));
+
+        // No subnet for this relay
+        let nak = request(Ipv4Addr::new(10, 0, 3, 1), &[0x52, 0x54, 0x01, 0x12, 0x34, 0x58]);
+        assert_eq!(nak.message_type(), Some(MessageType::Nak));
+    }
+
+    #[test]
+    fn test_ingress_subnet() {
+        let mut pools = SubnetPools::new();
+        pools.add_subnet(Ipv4Addr::new(10, 0, 1, 0), Ipv4Addr::new(255, 255, 255, 0), RangePool::new(Ipv4Addr::new(10, 0, 1, 10), Ipv4Addr::new(10, 0, 1, 20)));
+        pools.add_subnet(Ipv4Addr::new(10, 0, 2, 0), Ipv4Addr::new(255, 255, 255, 0), RangePool::new(Ipv4Addr::new(10, 0, 2, 10), Ipv4Addr::new(10, 0, 2, 20)));
+
+        let config = config(Ipv4Addr::new(10, 0, 1, 1));
+        let mut leases = LeaseStore::new();
+
+        let discover = FrameBuilder::new(Opcode::Request, 42)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+
+        // Broadcast on the second link of a multi-homed host, still
+        // identifying as the configured address
+        let offer = super::handle_as(&discover, &config, Ipv4Addr::new(10, 0, 2, 1), &mut pools, &mut leases).unwrap().unwrap();
+        assert_eq!(offer.your_ip(), Ipv4Addr::new(10, 0, 2, 10));
+        assert_eq!(offer.server_identifier(), Some(Ipv4Addr::new(10, 0, 1, 1)));
+
+        // Without a configured address the link's is the identifier
+        let config = ServerConfigBuilder::new().build();
+        let discover = FrameBuilder::new(Opcode::Request, 43)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x57])
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+
+        let offer = super::handle_as(&discover, &config, Ipv4Addr::new(10, 0, 1, 1), &mut pools, &mut leases).unwrap().unwrap();
+        assert_eq!(offer.your_ip(), Ipv4Addr::new(10, 0, 1, 10));
+        assert_eq!(offer.server_identifier(), Some(Ipv4Addr::new(10, 0, 1, 1)));
+    }
+
+    #[test]
+    fn test_subnet_selection() {
+        let mut pools = SubnetPools::new();
+        pools.add_subnet(Ipv4Addr::new(10, 0, 1, 0), Ipv4Addr::new(255, 255, 255, 0), RangePool::new(Ipv4Addr::new(10, 0, 1, 10), Ipv4Addr::new(10, 0, 1, 20)));
+        pools.add_subnet(Ipv4Addr::new(10, 0, 2, 0), Ipv4Addr::new(255, 255, 255, 0), RangePool::new(Ipv4Addr::new(10, 0, 2, 10), Ipv4Addr::new(10, 0, 2, 20)));
+
+        let config = config(Ipv4Addr::new(10, 0, 0, 1));
+        let mut leases = LeaseStore::new();
+
+        // Relayed from the first subnet, asking for the second
+        let mut discover = FrameBuilder::new(Opcode::Request, 42)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(MessageType::Discover)
+            .option(Option::ipv4(118, Ipv4Addr::new(10, 0, 2, 0)))
+            .build()
+            .unwrap();
+        discover.set_relay_ip(Ipv4Addr::new(10, 0, 1, 1));
+
+        assert_eq!(discover.subnet_selection(), Some(Ipv4Addr::new(10, 0, 2, 0)));
+
+        let offer = super::handle(&discover, &config, &mut pools, &mut leases).unwrap().unwrap();
+
+        assert_eq!(offer.your_ip(), Ipv4Addr::new(10, 0, 2, 10));
+        assert_eq!(offer.relay_ip(), Ipv4Addr::new(10, 0, 1, 1));
+        assert_eq!(offer.subnet_selection(), Some(Ipv4Addr::new(10, 0, 2, 0)));
+    }
+
+    #[test]
+    fn test_allowed_macs() {
+        let listed = [0x52, 0x54, 0x01, 0x12, 0x34, 0x56];
+        let unlisted = [0x52, 0x54, 0x01, 0x12, 0x34, 0x57];
+
+        let config = ServerConfigBuilder::new()
+            .server_ip(Ipv4Addr::new(10, 0, 0, 1))
+            .allow_mac(&listed)
+            .build();
+
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 9));
+        let mut leases = LeaseStore::new();
+
+        let discover = |mac: &[u8]| FrameBuilder::new(Opcode::Request, 42)
+            .client_mac(mac)
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+
+        assert!(super::handle(&discover(&unlisted), &config, &mut pool, &mut leases).unwrap().is_none());
+
+        let offer = super::handle(&discover(&listed), &config, &mut pool, &mut leases).unwrap().unwrap();
+        assert_eq!(offer.message_type(), Some(MessageType::Offer));
+
+        assert!(super::secure_eq(&listed, &listed));
+        assert!(!super::secure_eq(&listed, &unlisted));
+        assert!(!super::secure_eq(&listed, &listed[..5]));
+    }
+
+    /*
+     * Logger keeping every record, installed once for all tests
+     */
+    struct Capture;
+
+    static RECORDS: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+    static CAPTURE: Capture = Capture;
+
+    impl Log for Capture {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            RECORDS.lock().unwrap().push((record.level(), record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_parse_failure_logged() {
+        if log::set_logger(&CAPTURE).is_ok() {
+            log::set_max_level(LevelFilter::Debug);
+        }
+
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 9));
+        let mut leases = LeaseStore::new();
+        let src = "10.0.0.99:68".parse().unwrap();
+
+        let reply = super::respond(&[0x01, 0x02, 0x03], src, &config(Ipv4Addr::new(10, 0, 0, 1)), Ipv4Addr::new(10, 0, 0, 1), &mut pool, &mut leases).unwrap();
+        assert!(reply.is_none());
+
+        let records = RECORDS.lock().unwrap();
+        assert!(records.iter().any(|&(level, ref msg)| level == Level::Error && msg.starts_with("Failed to parse frame from 10.0.0.99:68")));
+    }
+
+    #[test]
+    fn test_no_address_no_reply() {
+        let discover = FrameBuilder::new(Opcode::Request, 42)
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+
+        // An empty pool
+        let mut pool = RangePool::new(Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 4));
+        assert!(super::handle(&discover, &config(Ipv4Addr::new(10, 0, 0, 1)), &mut pool, &mut LeaseStore::new()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_respond_to_parameter_list() {
+        let config = ServerConfigBuilder::new()
+            .server_ip(Ipv4Addr::new(10, 0, 0, 1))
+            .subnet_mask(Ipv4Addr::new(255, 255, 255, 0))
+            .router(Ipv4Addr::new(10, 0, 0, 254))
+            .dns_server(Ipv4Addr::new(10, 0, 0, 2))
+            .domain_name("example.org")
+            .build();
+
+        let discover = FrameBuilder::new(Opcode::Request, 42)
+            .message_type(MessageType::Discover)
+            .option(Option::parameter_request_list(&[1, 3, 6, 42]).unwrap())
+            .build()
+            .unwrap();
+
+        let offer = FrameBuilder::respond_to(&discover, &config).build().unwrap();
+
+        let mut tags: Vec<u8> = offer.options.iter().map(|opt| opt.tag).collect();
+        tags.sort();
+
+        assert_eq!(tags, [1, 3, 6, 51, 53, 54, 255]);
+        assert_eq!(offer.message_type(), Some(MessageType::Offer));
+        assert_eq!(offer.xid, 42);
+
+        // Without a list, everything configured is sent
+        let discover = FrameBuilder::new(Opcode::Request, 42)
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+
+        let offer = FrameBuilder::respond_to(&discover, &config).build().unwrap();
+        assert!(offer.option(15).is_some());
+    }
+    #[test]
+    fn test_respond_to_max_message_size() {
+        let mut config = ServerConfigBuilder::new()
+            .server_ip(Ipv4Addr::new(10, 0, 0, 1))
+            .subnet_mask(Ipv4Addr::new(255, 255, 255, 0))
+            .router(Ipv4Addr::new(10, 0, 0, 254))
+            .domain_name(&"a".repeat(60));
+
+        for i in 0..60 {
+            config = config.dns_server(Ipv4Addr::new(10, 0, 1, i));
+        }
+
+        let config = config.build();
+
+        let mut max_size = Option::new(::codes::OPTION_DHCP_MAX_MSG_SIZE);
+        max_size.set_data_u16(576).unwrap();
+
+        let discover = FrameBuilder::new(Opcode::Request, 42)
+            .message_type(MessageType::Discover)
+            .option(max_size)
+            .build()
+            .unwrap();
+
+        // Everything configured would go past 576 bytes, the domain name is
+        // the last option and gets dropped
+        let offer = FrameBuilder::respond_to(&discover, &config).build().unwrap();
+
+        assert!(offer.fits_in(576));
+        assert!(offer.option(15).is_none());
+        assert_eq!(offer.dns_servers().len(), 60);
+        assert!(offer.option(1).is_some());
+        assert_eq!(offer.message_type(), Some(MessageType::Offer));
+
+        // Without the limit everything is sent
+        let discover = FrameBuilder::new(Opcode::Request, 42)
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+
+        let offer = FrameBuilder::respond_to(&discover, &config).build().unwrap();
+        assert!(!offer.fits_in(576));
+        assert!(offer.option(15).is_some());
+    }
+}
@@ -0,0 +1,203 @@
+/*
+ * DHCPv6 messages (RFC 8415), client/server framing only
+ */
+
+use std::io::{Cursor, Read};
+use std::option;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use common::{Result, Error, ErrorKind};
+
+pub const DHCPV6_SOLICIT:    u8 = 1;
+pub const DHCPV6_ADVERTISE:  u8 = 2;
+pub const DHCPV6_REQUEST:    u8 = 3;
+pub const DHCPV6_REPLY:      u8 = 7;
+pub const DHCPV6_RELAY_FORW: u8 = 12;
+pub const DHCPV6_RELAY_REPL: u8 = 13;
+
+pub const OPTION_V6_CLIENT_ID:    u16 = 1;
+pub const OPTION_V6_SERVER_ID:    u16 = 2;
+pub const OPTION_V6_IA_NA:        u16 = 3;
+pub const OPTION_V6_IA_ADDR:      u16 = 5;
+pub const OPTION_V6_ORO:          u16 = 6;
+pub const OPTION_V6_ELAPSED_TIME: u16 = 8;
+pub const OPTION_V6_IA_PD:        u16 = 25;
+
+/*
+ * Represents a DHCPv6 option, with a 2 byte code and length
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct OptionV6 {
+    pub code: u16,
+    pub data: Vec<u8>
+}
+
+/*
+ * Represents a DHCPv6 client/server message
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct MessageV6 {
+    pub msg_type: u8,           // Message type
+    pub xid:      u32,          // Transaction ID, 24 bits
+    pub options:  Vec<OptionV6> // List of DHCPv6 options
+}
+
+impl OptionV6 {
+    /*
+     * Construct a DHCPv6 option
+     */
+    pub fn new(code: u16, data: Vec<u8>) -> OptionV6 {
+        OptionV6 {
+            code,
+            data
+        }
+    }
+}
+
+impl MessageV6 {
+    /*
+     * Construct a message without options
+     */
+    pub fn new(msg_type: u8, xid: u32) -> MessageV6 {
+        MessageV6 {
+            msg_type,
+            xid,
+            options: Vec::new()
+        }
+    }
+
+    /*
+     * Parse a client/server message. Relay messages use another framing and
+     * are rejected.
+     */
+    pub fn parse(buf: &[u8]) -> Result<MessageV6> {
+        if buf.len() < 4 {
+            return Err(Error::with_kind(ErrorKind::UnexpectedEof, "Message too short"));
+        }
+
+        let msg_type = buf[0];
+
+        if msg_type == DHCPV6_RELAY_FORW || msg_type == DHCPV6_RELAY_REPL {
+            return Err(Error::with_kind(ErrorKind::InvalidValue, "Relay messages are not supported"));
+        }
+
+        let xid = (buf[1] as u32) << 16 | (buf[2] as u32) << 8 | buf[3] as u32;
+
+        let mut cur = Cursor::new(&buf[4..]);
+        let mut options = Vec::new();
+
+        while (cur.position() as usize) < buf.len() - 4 {
+            let rest = buf.len() - 4 - cur.position() as usize;
+
+            if rest < 4 {
+                return Err(Error::with_kind(ErrorKind::UnexpectedEof, "Failed to parse option: Frame too short"));
+            }
+
+            let code = cur.read_u16::<BigEndian>()?;
+            let len = cur.read_u16::<BigEndian>()? as usize;
+
+            if len > rest - 4 {
+                return Err(Error::with_kind(ErrorKind::UnexpectedEof, "Failed to parse option: Option length exceeds buffer"));
+            }
+
+            let mut data = vec![0; len];
+            cur.read_exact(&mut data)?;
+
+            options.push(OptionV6::new(code, data));
+        }
+
+        Ok(MessageV6 {
+            msg_type,
+            xid,
+            options
+        })
+    }
+
+    /*
+     * Get an option
+     */
+    pub fn option(&self, code: u16) -> option::Option<&OptionV6> {
+        self.options.iter().find(|opt| opt.code == code)
+    }
+
+    /*
+     * Add an option to the message
+     */
+    pub fn add_option(&mut self, opt: OptionV6) {
+        self.options.push(opt);
+    }
+
+    /*
+     * Get the binary representation of a message
+     */
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.xid > 0xffffff {
+            return Err(Error::with_kind(ErrorKind::InvalidValue, "Transaction ID does not fit in 24 bits"));
+        }
+
+        let mut buf = vec![self.msg_type, (self.xid >> 16) as u8, (self.xid >> 8) as u8, self.xid as u8];
+
+        for opt in &self.options {
+            if opt.data.len() > 0xffff {
+                return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Option {} too long", opt.code)));
+            }
+
+            buf.write_u16::<BigEndian>(opt.code)?;
+            buf.write_u16::<BigEndian>(opt.data.len() as u16)?;
+            buf.extend_from_slice(&opt.data);
+        }
+
+        Ok(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MessageV6, OptionV6};
+
+    // SOLICIT with a DUID-LLT client id, an option request, elapsed time and IA_PD
+    const SOLICIT: [u8; 52] = [
+        0x01, 0x10, 0x08, 0x74,
+        0x00, 0x01, 0x00, 0x0e, 0x00, 0x01, 0x00, 0x01, 0x1c, 0x39, 0xcf, 0x88,
+        0x08, 0x00, 0x27, 0xfe, 0x8f, 0x95,
+        0x00, 0x06, 0x00, 0x04, 0x00, 0x17, 0x00, 0x18,
+        0x00, 0x08, 0x00, 0x02, 0x00, 0x00,
+        0x00, 0x19, 0x00, 0x0c, 0x27, 0xfe, 0x8f, 0x95, 0x00, 0x00, 0x0e, 0x10,
+        0x00, 0x00, 0x15, 0x18
+    ];
+
+    #[test]
+    fn test_parse_solicit() {
+        let msg = MessageV6::parse(&SOLICIT).unwrap();
+
+        assert_eq!(msg.msg_type, super::DHCPV6_SOLICIT);
+        assert_eq!(msg.xid, 0x100874);
+        assert_eq!(msg.options.len(), 4);
+        assert_eq!(msg.option(super::OPTION_V6_CLIENT_ID).unwrap().data, &SOLICIT[8..22]);
+        assert_eq!(msg.option(super::OPTION_V6_ORO).unwrap().data, [0x00, 0x17, 0x00, 0x18]);
+        assert_eq!(msg.option(super::OPTION_V6_IA_PD).unwrap().data.len(), 12);
+        assert!(msg.option(super::OPTION_V6_SERVER_ID).is_none());
+
+        assert_eq!(msg.to_bytes().unwrap(), SOLICIT.to_vec());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(MessageV6::parse(&SOLICIT[..3]).is_err());
+        assert!(MessageV6::parse(&SOLICIT[..10]).is_err());
+        assert!(MessageV6::parse(&SOLICIT[..6]).is_err());
+        assert!(MessageV6::parse(&[12, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_reply_to_bytes() {
+        let mut reply = MessageV6::new(super::DHCPV6_REPLY, 0x100874);
+        reply.add_option(OptionV6::new(super::OPTION_V6_SERVER_ID, vec![0x00, 0x03, 0x00, 0x01, 0x02]));
+
+        assert_eq!(reply.to_bytes().unwrap(), [0x07, 0x10, 0x08, 0x74, 0x00, 0x02, 0x00, 0x05, 0x00, 0x03, 0x00, 0x01, 0x02]);
+
+        reply.xid = 0x1000000;
+        assert!(reply.to_bytes().is_err());
+    }
+}
@@ -2,12 +2,26 @@
  * Writer - Construct DHCP packets
  */
 
-use std::error::Error as StdError;
 use std::vec::Vec;
+use std::io::Write;
+use std::net::Ipv4Addr;
+use std::option;
 
 use byteorder::{BigEndian, WriteBytesExt};
 
-use common::{Result, Error, Option, Frame};
+use common::{Result, Error, ErrorKind, Option, Frame, Flags, MessageType, Opcode, ClasslessRoute};
+use codes::{DHCP_MAGIC_COOKIE, HTYPE_ETHERNET, DHCP_MIN_MAX_MSG_SIZE, OPTION_PAD, OPTION_DHCP_MSG_TYPE, OPTION_ADDRESS_REQUEST, OPTION_ADDRESS_LEASE_TIME, OPTION_DHCP_SERVER_ID, OPTION_PARAMETER_LIST, OPTION_RENEWAL_TIME, OPTION_REBINDING_TIME, OPTION_RELAY_AGENT_INFO, OPTION_SUBNET_SELECTION, OPTION_DOMAIN_SEARCH, OPTION_CLASSLESS_ROUTE, OPTION_VENDOR_SPECIFIC, OPTION_CLASS_ID, OPTION_SERVER_NAME, OPTION_BOOTFILE_NAME, OPTION_END};
+
+/*
+ * Size of the IPv4 and UDP headers carrying a frame
+ */
+const IP_UDP_HEADERS_LEN: usize = 28;
+
+/*
+ * Options kept when a reply has to shrink to the client's maximum message
+ * size. A subnet selection option must be echoed (RFC 3011 section 3).
+ */
+const ESSENTIAL_OPTIONS: [u8; 5] = [OPTION_DHCP_MSG_TYPE, OPTION_DHCP_SERVER_ID, OPTION_ADDRESS_LEASE_TIME, OPTION_SUBNET_SELECTION, OPTION_END];
 
 impl Option {
     /*
@@ -15,18 +29,123 @@ impl Option {
      */
     pub fn new(tag: u8) -> Option {
         Option {
-            tag: tag,
+            tag,
             len: 0,
             data: Vec::new()
         }
     }
 
     /*
-     * Set an option's data
+     * Construct an option holding a single byte, e.g. the message type
+     */
+    pub fn u8(tag: u8, value: u8) -> Option {
+        Option { tag, len: 1, data: vec![value] }
+    }
+
+    /*
+     * Construct an option holding a 16 bit big endian integer
+     */
+    pub fn u16(tag: u8, value: u16) -> Option {
+        Option { tag, len: 2, data: value.to_be_bytes().to_vec() }
+    }
+
+    /*
+     * Construct an option holding a 32 bit big endian integer, e.g. a time
+     * in seconds
+     */
+    pub fn u32(tag: u8, value: u32) -> Option {
+        Option { tag, len: 4, data: value.to_be_bytes().to_vec() }
+    }
+
+    /*
+     * Construct an option holding an IPv4 address
+     */
+    pub fn ipv4(tag: u8, ip: Ipv4Addr) -> Option {
+        Option { tag, len: 4, data: ip.octets().to_vec() }
+    }
+
+    /*
+     * Construct an option holding a list of IPv4 addresses, at most 63 of
+     * them
+     */
+    pub fn ipv4_list(tag: u8, ips: &[Ipv4Addr]) -> Result<Option> {
+        let mut opt = Option::new(tag);
+        opt.set_data(ips.iter().flat_map(|ip| ip.octets()).collect())?;
+
+        Ok(opt)
+    }
+
+    /*
+     * Construct a Parameter Request List option (55) from option tags
+     */
+    pub fn parameter_request_list(tags: &[u8]) -> Result<Option> {
+        let mut opt = Option::new(OPTION_PARAMETER_LIST);
+        opt.set_data(tags.to_vec())?;
+
+        Ok(opt)
+    }
+
+    /*
+     * Construct a Domain Search option (119) from domain names, encoded
+     * without compression. Lists longer than 255 bytes need several
+     * instances, see Frame::set_domain_search.
+     */
+    pub fn domain_search(names: &[&str]) -> Result<Option> {
+        let mut opt = Option::new(OPTION_DOMAIN_SEARCH);
+        opt.set_data(encode_domain_search(names)?)?;
+
+        Ok(opt)
+    }
+
+    /*
+     * Construct a Classless Static Route option (121, RFC 3442) from
+     * destination, prefix length and router triples, sending only the
+     * significant octets of each destination. Use 0.0.0.0/0 for the default
+     * route. Routes longer than 255 bytes need several instances, see
+     * Frame::set_classless_routes.
+     */
+    pub fn classless_routes(routes: &[ClasslessRoute]) -> Result<Option> {
+        let mut opt = Option::new(OPTION_CLASSLESS_ROUTE);
+        opt.set_data(encode_classless_routes(routes)?)?;
+
+        Ok(opt)
+    }
+
+    /*
+     * Construct a Relay Agent Information option (82) from sub-option
+     * code/value pairs
+     */
+    pub fn relay_agent_info(subopts: &[(u8, Vec<u8>)]) -> Result<Option> {
+        let mut data = Vec::new();
+
+        for &(code, ref value) in subopts {
+            if value.len() > 255 {
+                return Err(Error::with_kind(ErrorKind::InvalidValue, "Sub-option too long"));
+            }
+
+            data.push(code);
+            data.push(value.len() as u8);
+            data.extend_from_slice(value);
+        }
+
+        let mut opt = Option::new(OPTION_RELAY_AGENT_INFO);
+        opt.set_data(data)?;
+
+        Ok(opt)
+    }
+
+    /*
+     * Set an option's data, at most 255 bytes
      */
-    pub fn set_data(&mut self, data: Vec<u8>) {
+    pub fn set_data(&mut self, data: Vec<u8>) -> Result<()> {
+        if data.len() > 255 {
+            return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Option data too long ({} bytes, maximum is 255)", data.len())));
+        }
+
         self.len = data.len() as u8;
         self.data = data;
+
+        Ok(())
     }
 
     /*
@@ -38,11 +157,10 @@ impl Option {
     }
 
     /*
-     * Set an option's data as a string
+     * Set an option's data as a string, at most 255 bytes
      */
-    pub fn set_data_str(&mut self, data: &str) {
-        self.len = data.len() as u8;
-        self.data = data.to_string().into_bytes();
+    pub fn set_data_str(&mut self, data: &str) -> Result<()> {
+        self.set_data(data.as_bytes().to_vec())
     }
 
     /*
@@ -60,10 +178,8 @@ impl Option {
         self.len = 2;
         self.data = Vec::new();
 
-        match self.data.write_u16::<BigEndian>(data) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(Error::new(e.description()))
-        }
+        self.data.write_u16::<BigEndian>(data)?;
+        Ok(())
     }
 
     /*
@@ -73,10 +189,8 @@ impl Option {
         self.len = 4;
         self.data = Vec::new();
 
-        match self.data.write_u32::<BigEndian>(data) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(Error::new(e.description()))
-        }
+        self.data.write_u32::<BigEndian>(data)?;
+        Ok(())
     }
 
     /*
@@ -86,23 +200,41 @@ impl Option {
         self.len = 8;
         self.data = Vec::new();
 
-        match self.data.write_u64::<BigEndian>(data) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(Error::new(e.description()))
-        }
+        self.data.write_u64::<BigEndian>(data)?;
+        Ok(())
     }
 
     /*
      * Get the binary representation of an option
      */
-    pub fn to_bytes(&self) -> Vec<u8> {
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
         let mut buf = Vec::with_capacity(2 + self.data.len());
 
         buf.push(self.tag);
-        buf.push(self.len);
-        buf.extend(self.data.iter());
 
-        buf
+        // Pad and End are a single byte
+        if self.tag == OPTION_PAD || self.tag == OPTION_END {
+            return Ok(buf);
+        }
+
+        let data = self.wire_data()?;
+        buf.push(data.len() as u8);
+        buf.extend(data.iter());
+
+        Ok(buf)
+    }
+
+    /*
+     * The data as serialized, the length byte is computed from it rather
+     * than taken from the len field. Data past 255 bytes cannot be encoded
+     * in one option (see Frame::set_concatenated_option).
+     */
+    fn wire_data(&self) -> Result<&[u8]> {
+        if self.data_len() > 255 {
+            return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Option {} data too long ({} bytes, maximum is 255)", self.tag, self.data_len())));
+        }
+
+        Ok(&self.data)
     }
 }
 
@@ -110,13 +242,13 @@ impl Frame {
     /*
      * Construct a classical (ethernet) DHCP frame
      */
-    pub fn new(op: u8, xid: u32) -> Frame {
+    pub fn new(op: Opcode, xid: u32) -> Frame {
         Frame {
-            op: op,
-            htype: 0x01,
+            op: op.into(),
+            htype: HTYPE_ETHERNET,
             hlen: 6,
             hops: 0,
-            xid: xid,
+            xid,
             secs: 0,
             flags: 0x00,
             ciaddr: vec![0; 4],
@@ -134,58 +266,1523 @@ impl Frame {
      * Construct a DHCP response
      */
     pub fn response(xid: u32, client_mac: Vec<u8>, client_ip: Vec<u8>, server_ip: Vec<u8>) -> Frame {
-        let mut f = Frame::new(2, xid);
+        let mut f = Frame::new(Opcode::Reply, xid);
         f.yiaddr = client_ip;
         f.siaddr = server_ip;
         f.chaddr = client_mac;
-        f.flags = 0x8000;
+        f.set_broadcast(true);
+
+        f
+    }
+
+    /*
+     * Construct the reply template to a request: opcode reply, with the
+     * transaction ID, hardware address, flags and relay address of the
+     * request. Addresses and options are left to the caller.
+     */
+    pub fn new_reply(request: &Frame) -> Frame {
+        let mut f = Frame::new(Opcode::Reply, request.xid);
+        f.htype = request.htype;
+        f.hlen = request.hlen;
+        f.flags = request.flags;
+        f.giaddr = request.giaddr.clone();
+        f.chaddr = request.chaddr.clone();
 
         f
     }
 
     /*
-     * Add an option to the frame
+     * Set or clear the broadcast flag, leaving the other bits untouched
+     */
+    pub fn set_broadcast(&mut self, broadcast: bool) {
+        let mut flags = self.bootp_flags();
+        flags.set_broadcast(broadcast);
+
+        self.set_bootp_flags(flags);
+    }
+
+    /*
+     * Set the flags field from Flags
+     */
+    pub fn set_bootp_flags(&mut self, flags: Flags) {
+        self.flags = flags.into();
+    }
+
+    /*
+     * Set a 6 byte client hardware address from its colon or hyphen
+     * separated text form, e.g. "52:54:01:12:34:56"
+     */
+    pub fn set_client_mac_str(&mut self, mac: &str) -> Result<()> {
+        let invalid = || Error::with_kind(ErrorKind::InvalidValue, format!("Invalid MAC address {}", mac));
+        let sep = if mac.contains('-') { '-' } else { ':' };
+
+        let mut bytes = Vec::with_capacity(16);
+
+        for part in mac.split(sep) {
+            if part.len() != 2 || !part.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+
+            bytes.push(u8::from_str_radix(part, 16).map_err(|_| invalid())?);
+        }
+
+        if bytes.len() != 6 {
+            return Err(invalid());
+        }
+
+        bytes.resize(16, 0);
+
+        self.hlen = 6;
+        self.chaddr = bytes;
+
+        Ok(())
+    }
+
+    /*
+     * Set the client IP address (ciaddr field)
+     */
+    pub fn set_client_ip(&mut self, ip: Ipv4Addr) {
+        self.ciaddr = ip.octets().to_vec();
+    }
+
+    /*
+     * Set the IP address assigned to the client (yiaddr field)
+     */
+    pub fn set_your_ip(&mut self, ip: Ipv4Addr) {
+        self.yiaddr = ip.octets().to_vec();
+    }
+
+    /*
+     * Set the next server IP address (siaddr field)
+     */
+    pub fn set_server_ip(&mut self, ip: Ipv4Addr) {
+        self.siaddr = ip.octets().to_vec();
+    }
+
+    /*
+     * Set the relay agent IP address (giaddr field)
+     */
+    pub fn set_relay_ip(&mut self, ip: Ipv4Addr) {
+        self.giaddr = ip.octets().to_vec();
+    }
+
+    /*
+     * Set the server host name (sname field, at most 64 bytes)
+     */
+    pub fn set_server_name(&mut self, name: &str) -> Result<()> {
+        set_fixed_string(&mut self.sname, 64, name)
+    }
+
+    /*
+     * Set the boot file name (file field, at most 128 bytes)
+     */
+    pub fn set_boot_file(&mut self, name: &str) -> Result<()> {
+        set_fixed_string(&mut self.file, 128, name)
+    }
+
+    /*
+     * Set the address lease time in seconds (option 51)
+     */
+    pub fn set_lease_time(&mut self, secs: u32) {
+        self.set_option_u32(OPTION_ADDRESS_LEASE_TIME, secs);
+    }
+
+    /*
+     * Set the renewal (T1) time in seconds (option 58)
+     */
+    pub fn set_renewal_time(&mut self, secs: u32) {
+        self.set_option_u32(OPTION_RENEWAL_TIME, secs);
+    }
+
+    /*
+     * Set the rebinding (T2) time in seconds (option 59)
+     */
+    pub fn set_rebinding_time(&mut self, secs: u32) {
+        self.set_option_u32(OPTION_REBINDING_TIME, secs);
+    }
+
+    /*
+     * Set the requested IP address (option 50)
+     */
+    pub fn set_requested_ip(&mut self, ip: Ipv4Addr) {
+        self.set_option_ipv4(OPTION_ADDRESS_REQUEST, ip);
+    }
+
+    /*
+     * Set the server identifier (option 54)
+     */
+    pub fn set_server_identifier(&mut self, ip: Ipv4Addr) {
+        self.set_option_ipv4(OPTION_DHCP_SERVER_ID, ip);
+    }
+
+    /*
+     * Set the vendor specific information (option 43)
+     */
+    pub fn set_vendor_specific(&mut self, data: Vec<u8>) -> Result<()> {
+        self.set_option_data(OPTION_VENDOR_SPECIFIC, data)
+    }
+
+    /*
+     * Set the vendor class identifier (option 60)
+     */
+    pub fn set_vendor_class_identifier(&mut self, id: &str) -> Result<()> {
+        self.set_option_data(OPTION_CLASS_ID, id.as_bytes().to_vec())
+    }
+
+    /*
+     * Set the TFTP server name (option 66)
+     */
+    pub fn set_tftp_server_name(&mut self, name: &str) -> Result<()> {
+        self.set_option_data(OPTION_SERVER_NAME, name.as_bytes().to_vec())
+    }
+
+    /*
+     * Set the boot file name (option 67)
+     */
+    pub fn set_bootfile_name(&mut self, name: &str) -> Result<()> {
+        self.set_option_data(OPTION_BOOTFILE_NAME, name.as_bytes().to_vec())
+    }
+
+    /*
+     * Set the domain search list (option 119), split across several
+     * instances if longer than 255 bytes (RFC 3397 section 2)
+     */
+    pub fn set_domain_search(&mut self, names: &[&str]) -> Result<()> {
+        let data = encode_domain_search(names)?;
+        self.set_concatenated_option(OPTION_DOMAIN_SEARCH, &data);
+
+        Ok(())
+    }
+
+    /*
+     * Set the classless static routes (option 121), split across several
+     * instances if longer than 255 bytes (RFC 3442 section 2)
+     */
+    pub fn set_classless_routes(&mut self, routes: &[ClasslessRoute]) -> Result<()> {
+        let data = encode_classless_routes(routes)?;
+        self.set_concatenated_option(OPTION_CLASSLESS_ROUTE, &data);
+
+        Ok(())
+    }
+
+    /*
+     * Point a network booting client at a TFTP server and boot file, in both
+     * the BOOTP fields (siaddr, file) and options 66 and 67
+     */
+    pub fn set_pxe(&mut self, next_server: Ipv4Addr, boot_file: &str) -> Result<()> {
+        self.set_boot_file(boot_file)?;
+        self.set_bootfile_name(boot_file)?;
+        self.set_tftp_server_name(&next_server.to_string())?;
+        self.set_server_ip(next_server);
+
+        Ok(())
+    }
+
+    /*
+     * Replace any option with the same tag by raw data
+     */
+    fn set_option_data(&mut self, tag: u8, data: Vec<u8>) -> Result<()> {
+        let mut opt = Option::new(tag);
+        opt.set_data(data)?;
+
+        self.set_option(opt);
+
+        Ok(())
+    }
+
+    /*
+     * Replace any option with the same tag by a 32 bit value
+     */
+    fn set_option_u32(&mut self, tag: u8, data: u32) {
+        self.set_option(Option::u32(tag, data));
+    }
+
+    /*
+     * Replace any option with the same tag by an IP address
+     */
+    fn set_option_ipv4(&mut self, tag: u8, ip: Ipv4Addr) {
+        self.set_option(Option::ipv4(tag, ip));
+    }
+
+    /*
+     * Set a single valued option: the first option with the same tag is
+     * replaced in place and any other removed, or the option is added if
+     * the frame has none
+     */
+    pub fn set_option(&mut self, opt: Option) {
+        match self.options.iter().position(|o| o.tag == opt.tag) {
+            Some(pos) => {
+                let tag = opt.tag;
+                self.options[pos] = opt;
+
+                let mut i = 0;
+                self.options.retain(|o| {
+                    i += 1;
+                    i <= pos + 1 || o.tag != tag
+                });
+            },
+            None => self.add_option(opt)
+        }
+    }
+
+    /*
+     * Set an option whose value may be longer than 255 bytes, split into
+     * consecutive instances as receivers concatenate them (RFC 3396), see
+     * concatenated_option. They take the place of the first existing
+     * instance and any other is removed.
+     */
+    pub fn set_concatenated_option(&mut self, tag: u8, data: &[u8]) {
+        let pos = self.options.iter().position(|o| o.tag == tag);
+        self.options.retain(|o| o.tag != tag);
+
+        let mut parts: Vec<Option> = data.chunks(255)
+            .map(|chunk| Option { tag, len: chunk.len() as u8, data: chunk.to_vec() })
+            .collect();
+
+        if parts.is_empty() {
+            parts.push(Option::new(tag));
+        }
+
+        match pos {
+            Some(pos) => {
+                let tail = self.options.split_off(pos);
+                self.options.extend(parts);
+                self.options.extend(tail);
+            },
+            None => parts.into_iter().for_each(|opt| self.add_option(opt))
+        }
+    }
+
+    /*
+     * Add an option to the frame, keeping a terminating End option last.
+     * Options that may appear once should use set_option.
      */
     pub fn add_option(&mut self, opt: Option) {
-        self.options.push(opt);
+        match self.options.last() {
+            Some(last) if last.tag == OPTION_END && opt.tag != OPTION_END => {
+                let pos = self.options.len() - 1;
+                self.options.insert(pos, opt);
+            },
+            _ => self.options.push(opt)
+        }
     }
 
     /*
-     * Get the binary representation of a frame
+     * Merge options from a template, e.g. per-client settings over a base
+     * set. Options whose tag the frame already has replace every existing
+     * instance if override_existing is set and are skipped otherwise. Pad
+     * and End are ignored.
      */
-    pub fn to_bytes(&self) -> Result<Vec<u8>> {
-        let mut buf = Vec::with_capacity(236);
+    pub fn apply_options(&mut self, others: &[Option], override_existing: bool) {
+        let present: Vec<u8> = self.options.iter().map(|opt| opt.tag).collect();
+        let mut replaced = Vec::new();
 
-        // One byte fields, first line
-        buf.push(self.op);
-        buf.push(self.htype);
-        buf.push(self.hlen);
-        buf.push(self.hops);
+        for opt in others {
+            if opt.tag == OPTION_PAD || opt.tag == OPTION_END {
+                continue;
+            }
 
-        // 2nd and 3rd line
-        try!(buf.write_u32::<BigEndian>(self.xid));
-        try!(buf.write_u16::<BigEndian>(self.secs));
-        try!(buf.write_u16::<BigEndian>(self.flags));
+            if present.contains(&opt.tag) {
+                if !override_existing {
+                    continue;
+                }
 
-        // Adresses
-        buf.extend(self.ciaddr.iter());
-        buf.extend(self.yiaddr.iter());
-        buf.extend(self.siaddr.iter());
-        buf.extend(self.giaddr.iter());
-        buf.extend(self.chaddr.iter());
+                // Remove the old instances once, keep all the new ones
+                if !replaced.contains(&opt.tag) {
+                    self.options.retain(|o| o.tag != opt.tag);
+                    replaced.push(opt.tag);
+                }
+            }
 
-        // Strings
-        buf.extend(self.sname.iter());
-        buf.extend(self.file.iter());
+            self.add_option(opt.clone());
+        }
+    }
+
+    /*
+     * Remove every instance of an option, returns whether any was present
+     */
+    pub fn remove_option(&mut self, tag: u8) -> bool {
+        let len = self.options.len();
+        self.options.retain(|o| o.tag != tag);
+
+        self.options.len() != len
+    }
+
+    /*
+     * Remove all the options, End included
+     */
+    pub fn clear_options(&mut self) {
+        self.options.clear();
+    }
+
+    /*
+     * Check that the frame is a well formed DHCP message: exactly one
+     * message type (option 53), a server identifier (option 54) on server
+     * messages, no chaddr bytes set past hlen, no option data longer than
+     * 255 bytes and a terminating End option
+     */
+    pub fn validate(&self) -> Result<()> {
+        match self.options.iter().filter(|opt| opt.tag == OPTION_DHCP_MSG_TYPE).count() {
+            0 => return Err(Error::with_kind(ErrorKind::InvalidOption, "Missing DHCP message type option (53)")),
+            1 => {},
+            n => return Err(Error::with_kind(ErrorKind::InvalidOption, format!("Duplicate DHCP message type option (53), found {} times", n)))
+        }
+
+        if self.opcode() == Opcode::Reply && self.option(OPTION_DHCP_SERVER_ID).is_none() {
+            return Err(Error::with_kind(ErrorKind::InvalidOption, "Missing server identifier option (54) on a server message"));
+        }
+
+        let hlen = (self.hlen as usize).min(self.chaddr.len());
+
+        if self.chaddr[hlen..].iter().any(|&b| b != 0) {
+            return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Client hardware address has non-zero bytes past hlen ({})", self.hlen)));
+        }
+
+        for opt in &self.options {
+            opt.wire_data()?;
+        }
+
+        match self.options.last() {
+            Some(opt) if opt.tag == OPTION_END => Ok(()),
+            _ => Err(Error::with_kind(ErrorKind::InvalidOption, "Options are not terminated by End (255)"))
+        }
+    }
+
+    /*
+     * Order the options by ascending tag, with Pad options moved before a
+     * terminating End. The sort is stable so split options (RFC 3396) keep
+     * their order.
+     */
+    pub fn sort_options(&mut self) {
+        self.options.sort_by_key(|opt| match opt.tag {
+            OPTION_END => 257,
+            OPTION_PAD => 256,
+            tag => tag as u16
+        });
+    }
+
+    /*
+     * Get the binary representation of a frame
+     */
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(240 + self.options_len());
+        self.write_to(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    /*
+     * Write the binary representation of a frame, without building it in
+     * memory first
+     */
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        self.write_header(w)?;
 
         // DHCP Magic cookie
-        buf.extend(vec![0x63, 0x82, 0x53, 0x63]);
+        w.write_all(&DHCP_MAGIC_COOKIE)?;
+
+        // Options, always terminated by End
+        write_options(w, &self.options)?;
 
-        // Options
-        for opt in self.options.iter() {
-            buf.extend(opt.to_bytes());
+        if !self.options.iter().any(|opt| opt.tag == OPTION_END) {
+            w.write_u8(OPTION_END)?;
         }
 
+        Ok(())
+    }
+
+    /*
+     * Get the binary representation of a plain BOOTP frame: the 236 byte
+     * header, without the magic cookie and options
+     */
+    pub fn to_bytes_bootp(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(236);
+        self.write_header(&mut buf)?;
+
         Ok(buf)
     }
+
+    /*
+     * Write the fixed BOOTP header fields. Only the first hlen bytes of
+     * chaddr are written, padded with zeros to its 16 bytes.
+     */
+    fn write_header<W: Write>(&self, w: &mut W) -> Result<()> {
+        if self.hlen > 16 {
+            return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Hardware address length too long ({} bytes, maximum is 16)", self.hlen)));
+        }
+
+        if self.chaddr.len() > 16 {
+            return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Client hardware address too long ({} bytes, maximum is 16)", self.chaddr.len())));
+        }
+
+        // One byte fields, first line
+        w.write_all(&[self.op, self.htype, self.hlen, self.hops])?;
+
+        // 2nd and 3rd line
+        w.write_u32::<BigEndian>(self.xid)?;
+        w.write_u16::<BigEndian>(self.secs)?;
+        w.write_u16::<BigEndian>(self.flags)?;
+
+        // Adresses
+        w.write_all(&self.ciaddr)?;
+        w.write_all(&self.yiaddr)?;
+        w.write_all(&self.siaddr)?;
+        w.write_all(&self.giaddr)?;
+        let hlen = (self.hlen as usize).min(self.chaddr.len());
+        w.write_all(&self.chaddr[..hlen])?;
+        w.write_all(&[0; 16][hlen..])?;
+
+        // Strings
+        w.write_all(&self.sname)?;
+        w.write_all(&self.file)?;
+
+        Ok(())
+    }
+
+    /*
+     * Size of the serialized options, including the End option appended by
+     * to_bytes
+     */
+    pub fn options_len(&self) -> usize {
+        let len: usize = self.options.iter().map(|opt| match opt.tag {
+            OPTION_PAD | OPTION_END => 1,
+            _ => 2 + opt.data_len()
+        }).sum();
+
+        if self.options.iter().any(|opt| opt.tag == OPTION_END) {
+            len
+        } else {
+            len + 1
+        }
+    }
+
+    /*
+     * Whether the serialized frame fits in a single IP packet of mtu bytes,
+     * counting the IP and UDP headers
+     */
+    pub fn fits_in(&self, mtu: usize) -> bool {
+        IP_UDP_HEADERS_LEN + 240 + self.options_len() <= mtu
+    }
+
+    /*
+     * Get the binary representation of a frame, failing if it does not fit
+     * in a single IP packet of mtu bytes (see fits_in)
+     */
+    pub fn to_bytes_within(&self, mtu: usize) -> Result<Vec<u8>> {
+        if !self.fits_in(mtu) {
+            return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Frame too large ({} bytes of options, MTU is {})", self.options_len(), mtu)));
+        }
+
+        self.to_bytes()
+    }
+
+    /*
+     * Get the binary representation of a frame, padded with zeros to at
+     * least min_len bytes (see BOOTP_MIN_LEN)
+     */
+    pub fn to_bytes_padded(&self, min_len: usize) -> Result<Vec<u8>> {
+        let mut buf = self.to_bytes()?;
+
+        if buf.len() < min_len {
+            buf.resize(min_len, 0);
+        }
+
+        Ok(buf)
+    }
+}
+
+/*
+ * Chainable construction of DHCP frames
+ */
+pub struct FrameBuilder {
+    frame: Frame,
+    error: option::Option<Error>,     // First error encountered, reported by build()
+    validate: bool,                   // Whether build() checks the frame with Frame::validate
+    max_size: option::Option<usize>   // Largest IP packet the frame may fill, see max_size()
+}
+
+impl FrameBuilder {
+    /*
+     * Start building a frame with an opcode and a transaction ID
+     */
+    pub fn new(op: Opcode, xid: u32) -> FrameBuilder {
+        FrameBuilder {
+            frame: Frame::new(op, xid),
+            error: None,
+            validate: false,
+            max_size: None
+        }
+    }
+
+    /*
+     * Start building the reply to a request from Frame::new_reply, kept
+     * within the maximum message size the client advertised (option 57)
+     */
+    pub fn reply_to(request: &Frame) -> FrameBuilder {
+        let builder = FrameBuilder {
+            frame: Frame::new_reply(request),
+            error: None,
+            validate: false,
+            max_size: None
+        };
+
+        match request.max_message_size() {
+            Some(size) => builder.max_size(size.max(DHCP_MIN_MAX_MSG_SIZE) as usize),
+            None => builder
+        }
+    }
+
+    /*
+     * Set the client hardware address
+     */
+    pub fn client_mac(mut self, mac: &[u8]) -> FrameBuilder {
+        if mac.len() > 16 {
+            self.fail(Error::with_kind(ErrorKind::InvalidValue, "Client hardware address too long"));
+            return self;
+        }
+
+        let mut chaddr = mac.to_vec();
+        chaddr.resize(16, 0);
+
+        self.frame.hlen = mac.len() as u8;
+        self.frame.chaddr = chaddr;
+        self
+    }
+
+    /*
+     * Set the flags field
+     */
+    pub fn flags(mut self, flags: u16) -> FrameBuilder {
+        self.frame.flags = flags;
+        self
+    }
+
+    /*
+     * Set the DHCP message type (option 53)
+     */
+    pub fn message_type(mut self, t: MessageType) -> FrameBuilder {
+        self.frame.add_option(Option::u8(OPTION_DHCP_MSG_TYPE, t.into()));
+        self
+    }
+
+    /*
+     * Set the IP address assigned to the client (yiaddr field)
+     */
+    pub fn your_ip(mut self, ip: Ipv4Addr) -> FrameBuilder {
+        self.frame.set_your_ip(ip);
+        self
+    }
+
+    /*
+     * Set the next server IP address (siaddr field)
+     */
+    pub fn server_ip(mut self, ip: Ipv4Addr) -> FrameBuilder {
+        self.frame.set_server_ip(ip);
+        self
+    }
+
+    /*
+     * Set the server identifier (option 54)
+     */
+    pub fn server_identifier(mut self, ip: Ipv4Addr) -> FrameBuilder {
+        self.frame.set_server_identifier(ip);
+        self
+    }
+
+    /*
+     * Set the address lease time in seconds (option 51)
+     */
+    pub fn lease_time(mut self, secs: u32) -> FrameBuilder {
+        self.frame.add_option(Option::u32(OPTION_ADDRESS_LEASE_TIME, secs));
+        self
+    }
+
+    /*
+     * Add an arbitrary option
+     */
+    pub fn option(mut self, opt: Option) -> FrameBuilder {
+        self.frame.add_option(opt);
+        self
+    }
+
+    /*
+     * Make build() fail if the frame is not a valid DHCP message, see
+     * Frame::validate
+     */
+    pub fn validate(mut self) -> FrameBuilder {
+        self.validate = true;
+        self
+    }
+
+    /*
+     * Limit the frame to an IP packet of size bytes, IP and UDP headers
+     * included (see Frame::fits_in). build() drops options other than the
+     * message type, server identifier, lease time and subnet selection, last
+     * added first, until the frame fits.
+     */
+    pub fn max_size(mut self, size: usize) -> FrameBuilder {
+        self.max_size = Some(size);
+        self
+    }
+
+    /*
+     * Terminate the option list with End and return the frame
+     */
+    pub fn build(mut self) -> Result<Frame> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        self.frame.add_option(Option::new(OPTION_END));
+
+        // Only the first hlen bytes of chaddr are meaningful
+        let hlen = (self.frame.hlen as usize).min(self.frame.chaddr.len());
+        for b in &mut self.frame.chaddr[hlen..] {
+            *b = 0;
+        }
+
+        if let Some(size) = self.max_size {
+            while !self.frame.fits_in(size) {
+                match self.frame.options.iter().rposition(|opt| !ESSENTIAL_OPTIONS.contains(&opt.tag)) {
+                    Some(i) => {
+                        let opt = self.frame.options.remove(i);
+                        debug!("Dropping option {} to fit in {} bytes", opt.tag, size);
+                    },
+                    None => return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Frame too large ({} bytes of options, maximum size is {})", self.frame.options_len(), size)))
+                }
+            }
+        }
+
+        if self.validate {
+            self.frame.validate()?;
+        }
+
+        Ok(self.frame)
+    }
+
+    /*
+     * Remember the first error so build() can report it
+     */
+    pub(crate) fn fail(&mut self, e: Error) {
+        if self.error.is_none() {
+            self.error = Some(e);
+        }
+    }
+}
+
+/*
+ * Get the binary representation of a list of options, outside of any frame.
+ * No End option is added.
+ */
+pub fn options_to_bytes(opts: &[Option]) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(opts.iter().map(|opt| 2 + opt.data.len()).sum());
+    write_options(&mut buf, opts)?;
+
+    Ok(buf)
+}
+
+/*
+ * Write options one after the other
+ */
+fn write_options<W: Write>(w: &mut W, opts: &[Option]) -> Result<()> {
+    for opt in opts {
+        w.write_u8(opt.tag)?;
+
+        // Pad and End are a single byte
+        if opt.tag != OPTION_PAD && opt.tag != OPTION_END {
+            let data = opt.wire_data()?;
+            w.write_u8(data.len() as u8)?;
+            w.write_all(data)?;
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Encode domain names as a Domain Search list (option 119), without
+ * compression
+ */
+fn encode_domain_search(names: &[&str]) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+
+    for name in names {
+        for label in name.split('.').filter(|label| !label.is_empty()) {
+            if label.len() > 63 {
+                return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Domain label too long ({} bytes, maximum is 63)", label.len())));
+            }
+
+            data.push(label.len() as u8);
+            data.extend_from_slice(label.as_bytes());
+        }
+
+        data.push(0);
+    }
+
+    Ok(data)
+}
+
+/*
+ * Encode routes as a Classless Static Route value (option 121)
+ */
+fn encode_classless_routes(routes: &[ClasslessRoute]) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+
+    for &(destination, prefix_len, router) in routes {
+        if prefix_len > 32 {
+            return Err(Error::with_kind(ErrorKind::InvalidValue, format!("Route prefix too long (/{}, maximum is /32)", prefix_len)));
+        }
+
+        let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+        let octets = (prefix_len as usize).div_ceil(8);
+
+        data.push(prefix_len);
+        data.extend_from_slice(&(u32::from(destination) & mask).to_be_bytes()[..octets]);
+        data.extend_from_slice(&router.octets());
+    }
+
+    Ok(data)
+}
+
+/*
+ * Write a string into a NUL padded fixed size field
+ */
+fn set_fixed_string(field: &mut Vec<u8>, size: usize, s: &str) -> Result<()> {
+    if s.len() > size {
+        return Err(Error::with_kind(ErrorKind::InvalidValue, format!("String too long ({} bytes, maximum is {})", s.len(), size)));
+    }
+
+    let mut buf = s.as_bytes().to_vec();
+    buf.resize(size, 0);
+    *field = buf;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_option_parameter_request_list() {
+        let opt = super::Option::parameter_request_list(&[1, 3, 6, 15]).unwrap();
+
+        assert_eq!(opt.to_bytes().unwrap(), [55, 4, 1, 3, 6, 15]);
+
+        let frame = super::FrameBuilder::new(super::Opcode::Request, 0).option(opt).build().unwrap();
+        let parsed = super::Frame::parse(&frame.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(parsed.requested_parameters(), [1, 3, 6, 15]);
+    }
+
+    #[test]
+    fn test_option_constructors() {
+        let opt = super::Option::u8(53, 1);
+
+        assert_eq!(opt.len, 1);
+        assert_eq!(opt.to_bytes().unwrap(), [53, 1, 1]);
+        assert_eq!(super::Option::u16(57, 1500).to_bytes().unwrap(), [57, 2, 0x05, 0xdc]);
+        assert_eq!(super::Option::u32(51, 3600).to_bytes().unwrap(), [51, 4, 0, 0, 0x0e, 0x10]);
+        assert_eq!(super::Option::ipv4(54, super::Ipv4Addr::new(10, 0, 0, 1)).to_bytes().unwrap(), [54, 4, 10, 0, 0, 1]);
+
+        let dns = super::Option::ipv4_list(6, &[super::Ipv4Addr::new(10, 0, 0, 2), super::Ipv4Addr::new(10, 0, 0, 3)]).unwrap();
+        assert_eq!(dns.to_bytes().unwrap(), [6, 8, 10, 0, 0, 2, 10, 0, 0, 3]);
+
+        assert!(super::Option::ipv4_list(6, &[super::Ipv4Addr::new(10, 0, 0, 2); 64]).is_err());
+    }
+
+    #[test]
+    fn test_option_data_too_long() {
+        let mut opt = super::Option::new(43);
+        opt.set_data(vec![1, 2, 3]).unwrap();
+
+        let e = opt.set_data(vec![0; 300]).unwrap_err();
+
+        assert_eq!(e.kind(), ::common::ErrorKind::InvalidValue);
+        assert_eq!(opt.len, 3);
+        assert_eq!(opt.data, [1, 2, 3]);
+        assert!(opt.set_data_str(&"a".repeat(300)).is_err());
+        assert!(opt.set_data(vec![0; 255]).is_ok());
+
+        // Data assigned directly is only caught when serializing
+        opt.data = vec![0; 300];
+        assert_eq!(opt.to_bytes().unwrap_err().kind(), ::common::ErrorKind::InvalidValue);
+        assert!(super::options_to_bytes(&[opt.clone()]).is_err());
+
+        let mut frame = super::Frame::new(super::Opcode::Request, 0);
+        frame.add_option(super::Option::u8(super::OPTION_DHCP_MSG_TYPE, 1));
+        frame.add_option(opt);
+        frame.add_option(super::Option::new(super::OPTION_END));
+
+        assert!(frame.validate().is_err());
+        assert!(frame.write_to(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_option_relay_agent_info() {
+        let opt = super::Option::relay_agent_info(&[(1, vec![0x00, 0x2a]), (2, b"rack1".to_vec())]).unwrap();
+
+        assert_eq!(opt.to_bytes().unwrap(), [82, 11, 1, 2, 0x00, 0x2a, 2, 5, 0x72, 0x61, 0x63, 0x6b, 0x31]);
+        assert!(super::Option::relay_agent_info(&[(1, vec![0; 256])]).is_err());
+        assert!(super::Option::relay_agent_info(&[(1, vec![0; 200]), (2, vec![0; 200])]).is_err());
+    }
+
+    #[test]
+    fn test_option_stale_len() {
+        let mut opt = super::Option::new(::codes::OPTION_ROUTER);
+        opt.set_data_ip(10, 0, 0, 1);
+        opt.len = 2;
+
+        assert_eq!(opt.data_len(), 4);
+        assert_eq!(opt.to_bytes().unwrap(), [3, 4, 10, 0, 0, 1]);
+
+        let mut frame = super::Frame::new(super::Opcode::Reply, 0);
+        frame.add_option(opt);
+
+        let parsed = super::Frame::parse(&frame.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.routers(), [super::Ipv4Addr::new(10, 0, 0, 1)]);
+        assert_eq!(parsed.options[0].len, 4);
+    }
+
+    #[test]
+    fn test_frame_option_data_too_long() {
+        let mut opt = super::Option::new(super::OPTION_DOMAIN_SEARCH);
+        opt.data = vec![0; 300];
+
+        let mut frame = super::Frame::new(super::Opcode::Reply, 0);
+        frame.add_option(opt);
+
+        // Neither cut to 255 bytes nor with a wrapped length byte
+        let e = frame.to_bytes().unwrap_err();
+        assert_eq!(e.kind(), ::common::ErrorKind::InvalidValue);
+        assert!(e.to_string().contains("Option 119"));
+    }
+
+    #[test]
+    fn test_options_to_bytes_round_trip() {
+        let mut lease = super::Option::new(super::OPTION_ADDRESS_LEASE_TIME);
+        lease.set_data_u32(3600).unwrap();
+
+        let mut router = super::Option::new(::codes::OPTION_ROUTER);
+        router.set_data_ip(10, 0, 0, 1);
+
+        let opts = vec![lease, router, super::Option::parameter_request_list(&[1, 3, 6]).unwrap()];
+        let bytes = super::options_to_bytes(&opts).unwrap();
+
+        assert_eq!(bytes.len(), 6 + 6 + 5);
+        assert_eq!(bytes[..6], [51, 4, 0, 0, 0x0e, 0x10]);
+        assert_eq!(::parser::parse_options(&bytes).unwrap(), opts);
+    }
+
+    #[test]
+    fn test_set_option() {
+        let mut frame = super::Frame::new(super::Opcode::Reply, 0);
+        frame.add_option(super::Option::new(super::OPTION_DHCP_MSG_TYPE));
+        frame.add_option(super::Option::new(super::OPTION_END));
+
+        let mut lease = super::Option::new(super::OPTION_ADDRESS_LEASE_TIME);
+        lease.set_data_u32(3600).unwrap();
+        frame.set_option(lease.clone());
+
+        lease.set_data_u32(7200).unwrap();
+        frame.set_option(lease);
+
+        let tags: Vec<u8> = frame.options.iter().map(|opt| opt.tag).collect();
+        assert_eq!(tags, [53, 51, 255]);
+        assert_eq!(frame.lease_time(), Some(7200));
+
+        // Duplicates added before are collapsed, in place
+        frame.add_option(super::Option::new(super::OPTION_DHCP_MSG_TYPE));
+        frame.set_option(super::Option::new(super::OPTION_DHCP_MSG_TYPE));
+
+        let tags: Vec<u8> = frame.options.iter().map(|opt| opt.tag).collect();
+        assert_eq!(tags, [53, 51, 255]);
+    }
+
+    #[test]
+    fn test_frame_lease_times() {
+        let mut frame = super::Frame::new(super::Opcode::Reply, 0);
+
+        frame.set_lease_time(3600);
+        frame.set_lease_time(86400);
+        frame.set_renewal_time(43200);
+        frame.set_rebinding_time(75600);
+
+        let parsed = super::Frame::parse(&frame.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(parsed.options.len(), 3);
+        assert_eq!(parsed.lease_time(), Some(86400));
+        assert_eq!(parsed.renewal_time(), Some(43200));
+        assert_eq!(parsed.rebinding_time(), Some(75600));
+        assert_eq!(super::Frame::new(super::Opcode::Reply, 0).lease_time(), None);
+    }
+
+    #[test]
+    fn test_frame_requested_ip_server_identifier() {
+        let mut frame = super::FrameBuilder::new(super::Opcode::Request, 0)
+            .message_type(super::MessageType::Request)
+            .build()
+            .unwrap();
+
+        frame.set_requested_ip(super::Ipv4Addr::new(192, 168, 1, 10));
+        frame.set_server_identifier(super::Ipv4Addr::new(192, 168, 1, 2));
+        frame.set_server_identifier(super::Ipv4Addr::new(192, 168, 1, 1));
+
+        // End stays last
+        assert_eq!(frame.options.last().unwrap().tag, 255);
+
+        let parsed = super::Frame::parse(&frame.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(parsed.options.len(), 3);
+        assert_eq!(parsed.requested_ip(), Some(super::Ipv4Addr::new(192, 168, 1, 10)));
+        assert_eq!(parsed.server_identifier(), Some(super::Ipv4Addr::new(192, 168, 1, 1)));
+    }
+
+    #[test]
+    fn test_frame_new_reply() {
+        let mut request = super::Frame::new(super::Opcode::Request, 0x1234);
+        request.set_client_mac_str("00:11:22:33:44:55").unwrap();
+        request.set_relay_ip(super::Ipv4Addr::new(10, 0, 0, 1));
+        request.set_broadcast(true);
+        request.add_option(super::Option::new(::codes::OPTION_DOMAIN_NAME));
+
+        let reply = super::Frame::new_reply(&request);
+
+        assert_eq!(reply.op, 2);
+        assert_eq!(reply.xid, 0x1234);
+        assert_eq!(reply.chaddr, request.chaddr);
+        assert_eq!(reply.hlen, 6);
+        assert_eq!(reply.relay_ip(), super::Ipv4Addr::new(10, 0, 0, 1));
+        assert!(reply.broadcast());
+        assert!(reply.options.is_empty());
+    }
+
+    #[test]
+    fn test_frame_addresses() {
+        let mut frame = super::Frame::new(super::Opcode::Reply, 0);
+
+        frame.set_your_ip(super::Ipv4Addr::new(192, 168, 1, 10));
+        frame.set_server_ip(super::Ipv4Addr::new(192, 168, 1, 1));
+
+        assert_eq!(frame.yiaddr, [192, 168, 1, 10]);
+        assert_eq!(frame.siaddr, [192, 168, 1, 1]);
+        assert_eq!(frame.your_ip(), super::Ipv4Addr::new(192, 168, 1, 10));
+        assert_eq!(frame.client_ip(), super::Ipv4Addr::new(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn test_frame_strings() {
+        let mut frame = super::Frame::new(super::Opcode::Reply, 0);
+
+        frame.set_server_name("tftp0").unwrap();
+        frame.set_boot_file("pxelinux.0").unwrap();
+
+        assert_eq!(frame.sname.len(), 64);
+        assert_eq!(frame.file.len(), 128);
+        assert_eq!(frame.server_name().unwrap().as_str(), "tftp0");
+        assert_eq!(frame.boot_file().unwrap().as_str(), "pxelinux.0");
+    }
+
+    #[test]
+    fn test_frame_strings_too_long() {
+        let mut frame = super::Frame::new(super::Opcode::Reply, 0);
+        let name = "a".repeat(65);
+
+        assert_eq!(frame.set_server_name(&name).unwrap_err().kind(), ::common::ErrorKind::InvalidValue);
+        assert!(frame.set_server_name(&name[..64]).is_ok());
+
+        let name = "a".repeat(129);
+
+        assert!(frame.set_boot_file(&name).is_err());
+        assert!(frame.set_boot_file(&name[..128]).is_ok());
+    }
+
+    #[test]
+    fn test_frame_end_appended() {
+        let mut frame = super::Frame::new(super::Opcode::Request, 0x1234);
+
+        let mut opt = super::Option::new(53);
+        opt.set_data_u8(1);
+        frame.add_option(opt);
+
+        let bytes = frame.to_bytes().unwrap();
+        let last = bytes.iter().rev().find(|&&b| b != 0x00).unwrap();
+
+        assert_eq!(*last, 0xff);
+        assert_eq!(bytes.iter().filter(|&&b| b == 0xff).count(), 1);
+
+        let parsed = super::Frame::parse(&bytes).unwrap();
+        assert_eq!(parsed.message_type(), Some(super::MessageType::Discover));
+        assert_eq!(parsed.options.len(), 1);
+    }
+
+    #[test]
+    fn test_frame_end_not_duplicated() {
+        let mut frame = super::Frame::new(super::Opcode::Request, 0);
+        frame.add_option(super::Option::new(255));
+
+        let bytes = frame.to_bytes().unwrap();
+
+        assert_eq!(bytes.len(), 241);
+        assert_eq!(bytes[240], 0xff);
+    }
+
+    #[test]
+    fn test_frame_pxe_offer() {
+        let mut offer = super::FrameBuilder::new(super::Opcode::Reply, 42)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(super::MessageType::Offer)
+            .your_ip(super::Ipv4Addr::new(10, 0, 0, 5))
+            .build()
+            .unwrap();
+
+        offer.set_pxe(super::Ipv4Addr::new(10, 0, 0, 2), "pxelinux.0").unwrap();
+        offer.set_vendor_class_identifier("PXEClient").unwrap();
+        offer.set_vendor_specific(vec![0x06, 0x01, 0x08]).unwrap();
+
+        let parsed = super::Frame::parse(&offer.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(parsed.server_ip(), super::Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!(parsed.boot_file().unwrap().as_str(), "pxelinux.0");
+        assert_eq!(parsed.tftp_server_name().unwrap().as_str(), "10.0.0.2");
+        assert_eq!(parsed.bootfile_name().unwrap().as_str(), "pxelinux.0");
+        assert_eq!(parsed.vendor_class_identifier().unwrap().as_str(), "PXEClient");
+        assert_eq!(parsed.vendor_specific().unwrap(), [0x06, 0x01, 0x08]);
+        assert!(offer.set_pxe(super::Ipv4Addr::new(10, 0, 0, 2), &"a".repeat(129)).is_err());
+    }
+
+    #[test]
+    fn test_frame_bootp_round_trip() {
+        let mut frame = super::Frame::response(42, vec![0x52, 0x54, 0x01, 0x12, 0x34, 0x56, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], vec![10, 0, 0, 5], vec![10, 0, 0, 1]);
+        frame.set_boot_file("boot.img").unwrap();
+
+        let bytes = frame.to_bytes_bootp().unwrap();
+        assert_eq!(bytes.len(), 236);
+
+        let parsed = super::Frame::parse_bootp(&bytes).unwrap();
+
+        assert_eq!(parsed, frame);
+        assert!(super::Frame::parse(&bytes).is_err());
+
+        // A DHCP frame is still accepted, with its options
+        let mut frame = super::Frame::new(super::Opcode::Request, 43);
+        frame.set_lease_time(60);
+
+        let parsed = super::Frame::parse_bootp(&frame.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.lease_time(), Some(60));
+    }
+
+    #[test]
+    fn test_frame_client_mac_str() {
+        let mut frame = super::Frame::new(super::Opcode::Request, 42);
+        frame.hlen = 16;
+
+        frame.set_client_mac_str("52:54:01:12:34:5a").unwrap();
+
+        assert_eq!(frame.hlen, 6);
+        assert_eq!(frame.chaddr, [0x52, 0x54, 0x01, 0x12, 0x34, 0x5a, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(frame.client_mac_string().as_str(), "52:54:01:12:34:5a");
+
+        frame.set_client_mac_str("AA-BB-CC-DD-EE-FF").unwrap();
+        assert_eq!(frame.hardware_address(), [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+
+        for mac in &["", "52:54:01:12:34", "52:54:01:12:34:56:78", "52:54:01:12:34:zz", "52:54-01:12:34:56", "525:4:01:12:34:56", "+2:54:01:12:34:56"] {
+            assert!(frame.set_client_mac_str(mac).is_err(), "{}", mac);
+        }
+
+        assert_eq!(frame.hardware_address(), [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+    }
+
+    #[test]
+    fn test_frame_write_to() {
+        let mut frame = super::FrameBuilder::new(super::Opcode::Request, 42)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(super::MessageType::Discover)
+            .build()
+            .unwrap();
+
+        frame.add_option(super::Option::new(0));
+
+        let mut buf = vec![0xaa];
+        frame.write_to(&mut buf).unwrap();
+
+        assert_eq!(buf[0], 0xaa);
+        assert_eq!(&buf[1..], frame.to_bytes().unwrap().as_slice());
+
+        // Write errors come back as our Error
+        let mut full = [0; 100];
+        let e = frame.write_to(&mut &mut full[..]).unwrap_err();
+
+        assert_eq!(e.kind(), ::common::ErrorKind::Io);
+    }
+
+    #[test]
+    fn test_frame_mtu() {
+        let mut frame = super::Frame::new(super::Opcode::Reply, 42);
+        assert_eq!(frame.options_len(), 1);
+
+        frame.set_lease_time(3600);
+        assert_eq!(frame.options_len(), 7);
+        assert!(frame.fits_in(576));
+
+        // Six options of 250 bytes are more than an Ethernet frame can carry
+        for tag in 224..230 {
+            let mut opt = super::Option::new(tag);
+            opt.set_data(vec![0; 250]).unwrap();
+            frame.add_option(opt);
+        }
+
+        assert_eq!(frame.options_len(), frame.to_bytes().unwrap().len() - 240);
+        assert!(!frame.fits_in(1500));
+        assert!(frame.to_bytes_within(1500).is_err());
+        assert!(frame.to_bytes_within(9000).is_ok());
+    }
+
+    #[test]
+    fn test_frame_broadcast_flag() {
+        let mut frame = super::Frame::new(super::Opcode::Request, 42);
+        assert!(!frame.broadcast());
+
+        frame.flags = 0x0001;
+        frame.set_broadcast(true);
+
+        assert!(frame.broadcast());
+        assert_eq!(frame.flags, 0x8001);
+
+        frame.set_broadcast(false);
+
+        assert!(!frame.broadcast());
+        assert_eq!(frame.flags, 0x0001);
+    }
+
+    #[test]
+    fn test_frame_padded() {
+        let frame = super::FrameBuilder::new(super::Opcode::Reply, 0)
+            .message_type(super::MessageType::Ack)
+            .build()
+            .unwrap();
+
+        let bytes = frame.to_bytes_padded(::codes::BOOTP_MIN_LEN).unwrap();
+
+        assert!(frame.to_bytes().unwrap().len() < 300);
+        assert_eq!(bytes.len(), 300);
+        assert_eq!(super::Frame::parse(&bytes).unwrap().message_type(), Some(super::MessageType::Ack));
+
+        // Frames already above the minimum are left untouched
+        assert_eq!(frame.to_bytes_padded(10).unwrap(), frame.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_builder_offer() {
+        let mac = [0x52, 0x54, 0x01, 0x12, 0x34, 0x56];
+
+        let frame = super::FrameBuilder::new(super::Opcode::Reply, 0x6e86444c)
+            .client_mac(&mac)
+            .message_type(super::MessageType::Offer)
+            .your_ip(super::Ipv4Addr::new(192, 168, 1, 10))
+            .server_ip(super::Ipv4Addr::new(192, 168, 1, 1))
+            .lease_time(3600)
+            .build()
+            .unwrap();
+
+        assert_eq!(frame.options.last().unwrap().tag, 255);
+
+        let bytes = frame.to_bytes().unwrap();
+        assert_eq!(*bytes.last().unwrap(), 0xff);
+
+        let parsed = super::Frame::parse(&bytes).unwrap();
+
+        assert_eq!(parsed.op, 2);
+        assert_eq!(parsed.xid, 0x6e86444c);
+        assert_eq!(parsed.client_mac_string().as_str(), "52:54:01:12:34:56");
+        assert_eq!(parsed.message_type(), Some(super::MessageType::Offer));
+        assert_eq!(parsed.your_ip(), super::Ipv4Addr::new(192, 168, 1, 10));
+        assert_eq!(parsed.server_ip(), super::Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(parsed.option(51).unwrap().data, [0x00, 0x00, 0x0e, 0x10]);
+        assert_eq!(parsed.options.len(), 2);
+    }
+
+    #[test]
+    fn test_builder_error() {
+        let result = super::FrameBuilder::new(super::Opcode::Reply, 0)
+            .client_mac(&[0; 17])
+            .build();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_sort_options() {
+        let mut first = super::Option::new(43);
+        first.set_data(vec![1]).unwrap();
+        let mut second = super::Option::new(43);
+        second.set_data(vec![2]).unwrap();
+
+        let mut frame = super::FrameBuilder::new(super::Opcode::Reply, 0)
+            .lease_time(3600)
+            .option(first)
+            .option(super::Option::new(0))
+            .option(second)
+            .message_type(super::MessageType::Ack)
+            .build()
+            .unwrap();
+
+        frame.set_server_identifier(super::Ipv4Addr::new(10, 0, 0, 1));
+        frame.sort_options();
+
+        let tags: Vec<u8> = frame.options.iter().map(|opt| opt.tag).collect();
+        assert_eq!(tags, [43, 43, 51, 53, 54, 0, 255]);
+        assert_eq!(frame.options[0].data, [1]);
+        assert_eq!(frame.options[1].data, [2]);
+
+        let bytes = frame.to_bytes().unwrap();
+        assert_eq!(bytes[240..246], [43, 1, 1, 43, 1, 2]);
+        assert_eq!(bytes[bytes.len() - 2..], [0, 255]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let ack = super::FrameBuilder::new(super::Opcode::Reply, 0)
+            .message_type(super::MessageType::Ack)
+            .server_identifier(super::Ipv4Addr::new(10, 0, 0, 1))
+            .validate()
+            .build()
+            .unwrap();
+        assert!(ack.validate().is_ok());
+
+        let e = super::FrameBuilder::new(super::Opcode::Request, 0).validate().build().unwrap_err();
+        assert_eq!(e.kind(), ::common::ErrorKind::InvalidOption);
+        assert!(e.msg.contains("Missing DHCP message type"));
+
+        let e = super::FrameBuilder::new(super::Opcode::Request, 0)
+            .message_type(super::MessageType::Discover)
+            .message_type(super::MessageType::Request)
+            .validate()
+            .build()
+            .unwrap_err();
+        assert!(e.msg.contains("Duplicate DHCP message type"));
+
+        let offer = super::FrameBuilder::new(super::Opcode::Reply, 0)
+            .message_type(super::MessageType::Offer)
+            .build()
+            .unwrap();
+        assert!(offer.validate().unwrap_err().msg.contains("server identifier"));
+
+        let mut discover = super::Frame::new(super::Opcode::Request, 0);
+        discover.add_option(super::Option::new(53));
+        assert!(discover.validate().unwrap_err().msg.contains("End"));
+    }
+
+    #[test]
+    fn test_reply_to() {
+        let mut max_size = super::Option::new(::codes::OPTION_DHCP_MAX_MSG_SIZE);
+        max_size.set_data_u16(300).unwrap();
+
+        let request = super::FrameBuilder::new(super::Opcode::Request, 42)
+            .client_mac(&[0x52, 0x54, 0x01, 0x12, 0x34, 0x56])
+            .message_type(super::MessageType::Discover)
+            .option(max_size)
+            .build()
+            .unwrap();
+
+        // A limit below the 576 bytes every client accepts is raised to it
+        let reply = super::FrameBuilder::reply_to(&request)
+            .message_type(super::MessageType::Offer)
+            .option(super::Option::ipv4_list(6, &[super::Ipv4Addr::new(10, 0, 0, 2); 63]).unwrap())
+            .option(super::Option::ipv4_list(6, &[super::Ipv4Addr::new(10, 0, 0, 3); 20]).unwrap())
+            .build()
+            .unwrap();
+
+        assert_eq!(reply.opcode(), super::Opcode::Reply);
+        assert_eq!(reply.xid, 42);
+        assert_eq!(reply.hardware_address(), request.hardware_address());
+        assert!(reply.fits_in(576));
+        assert_eq!(reply.options.len(), 3);
+    }
+
+    #[test]
+    fn test_max_size() {
+        // Essential options that do not fit are an error
+        let e = super::FrameBuilder::new(super::Opcode::Reply, 42)
+            .message_type(super::MessageType::Offer)
+            .max_size(200)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(e.kind(), super::ErrorKind::InvalidValue);
+
+        // The subnet selection option goes back even when added last
+        let subnet = super::Ipv4Addr::new(10, 0, 2, 0);
+        let offer = super::FrameBuilder::new(super::Opcode::Reply, 42)
+            .message_type(super::MessageType::Offer)
+            .server_identifier(super::Ipv4Addr::new(10, 0, 0, 1))
+            .lease_time(3600)
+            .option(super::Option::ipv4(::codes::OPTION_ROUTER, super::Ipv4Addr::new(10, 0, 2, 1)))
+            .option(super::Option::ipv4(super::OPTION_SUBNET_SELECTION, subnet))
+            .max_size(290)
+            .build()
+            .unwrap();
+
+        assert!(offer.option(::codes::OPTION_ROUTER).is_none());
+        assert_eq!(offer.subnet_selection(), Some(subnet));
+    }
+
+    #[test]
+    fn test_remove_option() {
+        let mut frame = super::FrameBuilder::new(super::Opcode::Request, 0)
+            .message_type(super::MessageType::Discover)
+            .option(super::Option::new(60))
+            .option(super::Option::new(60))
+            .build()
+            .unwrap();
+
+        assert!(frame.remove_option(60));
+        assert!(!frame.remove_option(60));
+        assert!(frame.option(60).is_none());
+        assert_eq!(frame.message_type(), Some(super::MessageType::Discover));
+
+        frame.clear_options();
+        assert!(frame.options.is_empty());
+
+        // Reused as a template
+        frame.add_option(super::Option::new(255));
+        frame.set_server_identifier(super::Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(frame.options.len(), 2);
+        assert_eq!(frame.options[1].tag, 255);
+    }
+
+    #[test]
+    fn test_domain_search() {
+        let mut frame = super::Frame::new(super::Opcode::Reply, 0);
+        frame.set_domain_search(&["eng.example.com", "example.com"]).unwrap();
+
+        assert_eq!(frame.option(119).unwrap().data, [
+            0x03, 0x65, 0x6e, 0x67, 0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00,
+            0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00
+        ]);
+
+        let parsed = super::Frame::parse(&frame.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.domain_search().unwrap(), ["eng.example.com", "example.com"]);
+
+        assert!(super::Option::domain_search(&[&"a".repeat(64)]).is_err());
+    }
+
+    #[test]
+    fn test_domain_search_long() {
+        // 20 names of 16 bytes once encoded, too long for a single option
+        let names: Vec<String> = (0..20).map(|i| format!("host{:02}.example", i)).collect();
+        let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+
+        assert!(super::Option::domain_search(&names).is_err());
+
+        let mut frame = super::Frame::new(super::Opcode::Reply, 0);
+        frame.set_domain_search(&["example.com"]).unwrap();
+        frame.set_domain_search(&names).unwrap();
+
+        let parts: Vec<usize> = frame.options.iter().filter(|opt| opt.tag == 119).map(|opt| opt.data.len()).collect();
+        assert_eq!(parts, [255, 65]);
+
+        let parsed = super::Frame::parse(&frame.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.domain_search().unwrap(), names);
+    }
+
+    #[test]
+    fn test_classless_routes() {
+        let routes = [
+            (super::Ipv4Addr::new(10, 0, 0, 0), 8, super::Ipv4Addr::new(192, 168, 1, 1)),
+            (super::Ipv4Addr::new(0, 0, 0, 0), 0, super::Ipv4Addr::new(192, 168, 1, 254))
+        ];
+
+        let mut frame = super::Frame::new(super::Opcode::Reply, 0);
+        frame.set_classless_routes(&routes).unwrap();
+
+        assert_eq!(frame.option(super::OPTION_CLASSLESS_ROUTE).unwrap().data, [8, 10, 192, 168, 1, 1, 0, 192, 168, 1, 254]);
+
+        let parsed = super::Frame::parse(&frame.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.classless_routes().unwrap(), routes);
+
+        // Host bits past the prefix are not sent
+        let opt = super::Option::classless_routes(&[(super::Ipv4Addr::new(172, 16, 5, 9), 12, super::Ipv4Addr::new(10, 0, 0, 1))]).unwrap();
+        assert_eq!(opt.data, [12, 172, 16, 10, 0, 0, 1]);
+
+        let e = super::Option::classless_routes(&[(super::Ipv4Addr::new(10, 0, 0, 1), 33, super::Ipv4Addr::new(10, 0, 0, 1))]).unwrap_err();
+        assert_eq!(e.kind(), super::ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn test_classless_routes_long() {
+        // 40 /24 routes of 8 bytes each, split across two instances
+        let routes: Vec<_> = (0..40).map(|i| (super::Ipv4Addr::new(10, i, 0, 0), 24, super::Ipv4Addr::new(192, 168, 1, 1))).collect();
+
+        let mut frame = super::Frame::new(super::Opcode::Reply, 0);
+        frame.set_classless_routes(&routes).unwrap();
+
+        assert_eq!(frame.options.iter().filter(|opt| opt.tag == super::OPTION_CLASSLESS_ROUTE).count(), 2);
+
+        let parsed = super::Frame::parse(&frame.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.classless_routes().unwrap(), routes);
+    }
+
+    #[test]
+    fn test_apply_options() {
+        let mut dns = super::Option::new(6);
+        dns.set_data_ip(10, 0, 0, 2);
+        let mut domain = super::Option::new(15);
+        domain.set_data_str("example.org").unwrap();
+
+        let template = [dns, domain, super::Option::new(255)];
+
+        let client = || {
+            let mut frame = super::FrameBuilder::new(super::Opcode::Reply, 0)
+                .message_type(super::MessageType::Ack)
+                .build()
+                .unwrap();
+            frame.set_option_data(15, b"lab.example.org".to_vec()).unwrap();
+            frame
+        };
+
+        let mut kept = client();
+        kept.apply_options(&template, false);
+
+        assert_eq!(kept.domain_name().unwrap(), "lab.example.org");
+        assert_eq!(kept.dns_servers(), [super::Ipv4Addr::new(10, 0, 0, 2)]);
+
+        let mut overridden = client();
+        overridden.apply_options(&template, true);
+
+        assert_eq!(overridden.domain_name().unwrap(), "example.org");
+        assert_eq!(overridden.options.iter().filter(|opt| opt.tag == 15).count(), 1);
+        assert_eq!(overridden.options.iter().filter(|opt| opt.tag == 255).count(), 1);
+        assert_eq!(overridden.options.last().unwrap().tag, 255);
+    }
+
+    #[test]
+    fn test_hardware_address_length() {
+        let mut frame = super::Frame::new(super::Opcode::Request, 0);
+        frame.hlen = 20;
+
+        let e = frame.to_bytes().unwrap_err();
+        assert_eq!(e.kind(), ::common::ErrorKind::InvalidValue);
+        assert!(e.msg.contains("Hardware address length too long (20 bytes"));
+
+        frame.hlen = 6;
+        frame.chaddr = vec![0; 17];
+        assert!(frame.to_bytes().is_err());
+
+        // Short chaddr padded on the wire
+        let frame = super::Frame::response(1, vec![0x52, 0x54, 0x01, 0x12, 0x34, 0x56], vec![10, 0, 0, 5], vec![10, 0, 0, 1]);
+        let parsed = super::Frame::parse(&frame.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.client_mac_string(), "52:54:01:12:34:56");
+        assert_eq!(parsed.chaddr.len(), 16);
+
+        // Garbage past hlen
+        let mut frame = super::FrameBuilder::new(super::Opcode::Request, 0)
+            .message_type(super::MessageType::Discover)
+            .build()
+            .unwrap();
+        frame.chaddr[10] = 0xff;
+        assert!(frame.validate().unwrap_err().msg.contains("past hlen"));
+
+        // and never sent
+        let parsed = super::Frame::parse(&frame.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.chaddr, [0; 16]);
+    }
 }
@@ -0,0 +1,165 @@
+/*
+ * Parse DHCP frames stored in pcap files
+ *
+ * The fixtures are classic libpcap files (not pcapng) with Ethernet link
+ * layer; only the UDP payloads sent to or from the DHCP ports are kept.
+ * synthetic_discover_offer.pcap is not a capture: it was written by hand to
+ * look like a typical DISCOVER/OFFER exchange, so it checks the reader and
+ * the parser against the pcap format rather than against real clients.
+ */
+
+extern crate byteorder;
+extern crate dhcp;
+
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::Path;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use dhcp::common::{Frame, MessageType, Opcode};
+
+/*
+ * Magic numbers of the pcap global header, microsecond and nanosecond
+ * resolution
+ */
+const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+const PCAP_MAGIC_NS: u32 = 0xa1b23c4d;
+
+/*
+ * Sizes of the pcap headers
+ */
+const GLOBAL_HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+
+/*
+ * Link and network layer constants
+ */
+const LINKTYPE_ETHERNET: u32 = 1;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const IPPROTO_UDP: u8 = 17;
+
+/*
+ * DHCP server and client ports
+ */
+const SERVER_PORT: u16 = 67;
+const CLIENT_PORT: u16 = 68;
+
+/*
+ * Read the DHCP payloads of every packet in a pcap file, in capture order
+ */
+fn dhcp_payloads<P: AsRef<Path>>(path: P) -> Vec<Vec<u8>> {
+    let data = fs::read(path).unwrap();
+    assert!(data.len() >= GLOBAL_HEADER_LEN, "truncated pcap global header");
+
+    // The writer's byte order is given by how the magic number reads
+    let little = match LittleEndian::read_u32(&data) {
+        PCAP_MAGIC | PCAP_MAGIC_NS => true,
+        _ => match BigEndian::read_u32(&data) {
+            PCAP_MAGIC | PCAP_MAGIC_NS => false,
+            magic => panic!("not a pcap file (magic {:#x})", magic)
+        }
+    };
+
+    let read_u32 = |buf: &[u8]| if little { LittleEndian::read_u32(buf) } else { BigEndian::read_u32(buf) };
+    assert_eq!(read_u32(&data[20..24]), LINKTYPE_ETHERNET, "unsupported link type");
+
+    let mut payloads = Vec::new();
+    let mut pos = GLOBAL_HEADER_LEN;
+
+    while pos + RECORD_HEADER_LEN <= data.len() {
+        let caplen = read_u32(&data[pos + 8..pos + 12]) as usize;
+        let start = pos + RECORD_HEADER_LEN;
+
+        assert!(start + caplen <= data.len(), "truncated pcap record");
+
+        if let Some(payload) = udp_payload(&data[start..start + caplen]) {
+            payloads.push(payload.to_vec());
+        }
+
+        pos = start + caplen;
+    }
+
+    payloads
+}
+
+/*
+ * Extract the payload of an Ethernet/IPv4/UDP packet between the DHCP ports,
+ * None for any other packet
+ */
+fn udp_payload(packet: &[u8]) -> Option<&[u8]> {
+    let mut offset = 12;
+
+    if packet.len() < offset + 2 {
+        return None;
+    }
+
+    let mut ethertype = BigEndian::read_u16(&packet[offset..]);
+    offset += 2;
+
+    // Skip an 802.1Q tag
+    if ethertype == ETHERTYPE_VLAN && packet.len() >= offset + 4 {
+        ethertype = BigEndian::read_u16(&packet[offset + 2..]);
+        offset += 4;
+    }
+
+    if ethertype != ETHERTYPE_IPV4 {
+        return None;
+    }
+
+    let ip = packet.get(offset..)?;
+    if ip.len() < 20 || ip[0] >> 4 != 4 || ip[9] != IPPROTO_UDP {
+        return None;
+    }
+
+    let header_len = ((ip[0] & 0x0f) as usize) * 4;
+    let total_len = (BigEndian::read_u16(&ip[2..]) as usize).min(ip.len());
+
+    let udp = ip.get(header_len..total_len)?;
+    if udp.len() < 8 {
+        return None;
+    }
+
+    let ports = [BigEndian::read_u16(&udp[0..]), BigEndian::read_u16(&udp[2..])];
+    if !ports.iter().all(|&p| p == SERVER_PORT || p == CLIENT_PORT) {
+        return None;
+    }
+
+    let udp_len = (BigEndian::read_u16(&udp[4..]) as usize).min(udp.len());
+    udp.get(8..udp_len)
+}
+
+#[test]
+fn test_pcap_synthetic_discover_offer() {
+    let payloads = dhcp_payloads(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/synthetic_discover_offer.pcap"));
+    assert_eq!(payloads.len(), 2);
+
+    let frames = payloads.iter()
+        .map(|p| Frame::parse(p).unwrap())
+        .collect::<Vec<_>>();
+
+    let discover = &frames[0];
+    assert_eq!(discover.opcode(), Opcode::Request);
+    assert_eq!(discover.message_type(), Some(MessageType::Discover));
+    assert_eq!(discover.client_mac_string(), "52:54:00:12:34:56");
+    assert_eq!(discover.host_name(), Some("host".to_string()));
+    assert_eq!(discover.requested_parameters(), vec![1, 3, 6, 15, 28, 51, 58]);
+
+    let offer = &frames[1];
+    assert_eq!(offer.opcode(), Opcode::Reply);
+    assert_eq!(offer.message_type(), Some(MessageType::Offer));
+    assert_eq!(offer.xid(), discover.xid());
+    assert_eq!(offer.hardware_address(), discover.hardware_address());
+    assert_eq!(offer.your_ip(), Ipv4Addr::new(192, 168, 1, 10));
+    assert_eq!(offer.server_identifier(), Some(Ipv4Addr::new(192, 168, 1, 1)));
+    assert_eq!(offer.subnet_mask(), Some(Ipv4Addr::new(255, 255, 255, 0)));
+    assert_eq!(offer.routers(), vec![Ipv4Addr::new(192, 168, 1, 1)]);
+    assert_eq!(offer.lease_time(), Some(3600));
+    assert_eq!(offer.domain_name(), Some("example.org".to_string()));
+
+    // Serializing again yields the same frames
+    for frame in &frames {
+        assert!(Frame::parse(&frame.to_bytes().unwrap()).unwrap().logical_eq(frame));
+    }
+}